@@ -0,0 +1,224 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use inquire::{validator::Validation, Confirm, Text};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// The answers of the `create` prompts, they come from the flags or the answers file
+#[derive(Args, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Answers {
+    /// The project name
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// The project description
+    #[arg(long)]
+    pub description: Option<String>,
+
+    /// The build command of the project
+    #[arg(long)]
+    pub build_cmd: Option<String>,
+
+    /// The dist of the project
+    #[arg(long)]
+    pub dist: Option<String>,
+
+    /// The template, a name on the server, a local path or a git url, followed by an optional
+    /// `#ref`, `#ref:subdir` or `#:subdir`, e.g. `git@host:yoo/templates.git#v1.0:react`
+    #[arg(long, value_name = "NAME|PATH|GIT-URL[#[REF][:SUBDIR]]")]
+    pub template: Option<String>,
+
+    /// Clean up the project directory if it is not empty
+    #[arg(long)]
+    pub clean: bool,
+
+    /// Set a template variable, can be used multiple times
+    #[arg(long = "var", value_name = "KEY=VALUE", value_parser = parse_var)]
+    #[serde(deserialize_with = "deserialize_vars")]
+    pub vars: Vec<(String, String)>,
+}
+
+impl Answers {
+    /// read the answers from a toml or json file
+    pub fn from_file(path: &Path) -> Result<Answers> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the answers file {}", path.display()))?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&content)
+                .with_context(|| format!("Failed to parse the answers file {}", path.display())),
+            Some("json") => serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse the answers file {}", path.display())),
+            _ => Err(anyhow::Error::msg(
+                "The answers file must be a `.toml` or `.json` file",
+            )),
+        }
+    }
+
+    /// fill the missing answers with the ones from the other answers, the flags win over the file
+    pub fn or(self, other: Answers) -> Answers {
+        Answers {
+            name: self.name.or(other.name),
+            description: self.description.or(other.description),
+            build_cmd: self.build_cmd.or(other.build_cmd),
+            dist: self.dist.or(other.dist),
+            template: self.template.or(other.template),
+            clean: self.clean || other.clean,
+            vars: other.vars.into_iter().chain(self.vars).collect(),
+        }
+    }
+}
+
+fn parse_var(input: &str) -> Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("`{}` must be in the form KEY=VALUE", input)),
+    }
+}
+
+// the answers file declares the variables as a table
+fn deserialize_vars<'de, D>(deserializer: D) -> Result<Vec<(String, String)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(BTreeMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .collect())
+}
+
+pub(crate) fn validate_project_name(name: &str) -> Result<(), String> {
+    let pattern = Regex::new(r"^[A-Za-z0-9]+(-[A-Za-z0-9]+)*$").unwrap();
+    if pattern.is_match(name) {
+        Ok(())
+    } else {
+        Err("Your project name is invalid it must match the regexp `^[A-Za-z0-9]+(-[A-Za-z0-9]+)*$`".into())
+    }
+}
+
+/// use the given answer, otherwise prompt for it or fail in non-interactive mode
+pub(crate) fn ask_text<F>(
+    answer: Option<String>,
+    flag: &str,
+    message: &str,
+    default: Option<&str>,
+    interactive: bool,
+    validator: F,
+) -> Result<String>
+where
+    F: Fn(&str) -> Result<(), String> + Clone + 'static,
+{
+    if let Some(answer) = answer {
+        let answer = answer.trim().to_string();
+        validator(&answer).map_err(anyhow::Error::msg)?;
+        return Ok(answer);
+    }
+
+    if !interactive {
+        return match default {
+            Some(default) => Ok(default.to_string()),
+            None => Err(anyhow::Error::msg(format!(
+                "Missing value for `--{}`, set it with the flag or in the answers file",
+                flag
+            ))),
+        };
+    }
+
+    let mut text = Text::new(message).with_validator(move |input: &str| {
+        Ok(match validator(input.trim()) {
+            Ok(_) => Validation::Valid,
+            Err(msg) => Validation::Invalid(msg.into()),
+        })
+    });
+
+    if let Some(default) = default {
+        text = text.with_default(default);
+    }
+
+    let answer = text
+        .prompt()
+        .with_context(|| "Failed to interact with the user")?;
+
+    Ok(answer.trim().to_string())
+}
+
+/// use the given answer if it is confirmed, otherwise prompt for it or fail in non-interactive mode
+pub(crate) fn ask_confirm(
+    answer: bool,
+    flag: &str,
+    message: &str,
+    interactive: bool,
+) -> Result<bool> {
+    if answer {
+        return Ok(true);
+    }
+
+    if !interactive {
+        return Err(anyhow::Error::msg(format!(
+            "{} Pass `--{}` to confirm it in non-interactive mode",
+            message, flag
+        )));
+    }
+
+    Confirm::new(message)
+        .with_default(false)
+        .prompt()
+        .with_context(|| "Failed to interact with the user")
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{ask_text, validate_project_name, Answers};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_flags_win_over_file() {
+        let file: Answers = toml::from_str(
+            r#"
+            name = "from-file"
+            dist = "dist"
+            clean = true
+
+            [vars]
+            title = "from file"
+            "#,
+        )
+        .unwrap();
+
+        let flags = Answers {
+            name: Some("from-flag".to_string()),
+            vars: vec![("title".to_string(), "from flag".to_string())],
+            ..Default::default()
+        };
+
+        let answers = flags.or(file);
+        assert_eq!(answers.name.as_deref(), Some("from-flag"));
+        assert_eq!(answers.dist.as_deref(), Some("dist"));
+        assert!(answers.clean);
+
+        let vars: BTreeMap<_, _> = answers.vars.into_iter().collect();
+        assert_eq!(vars["title"], "from flag");
+    }
+
+    #[test]
+    fn test_non_interactive() {
+        assert!(ask_text(None, "name", "", None, false, validate_project_name).is_err());
+        assert!(ask_text(
+            Some("bad name".to_string()),
+            "name",
+            "",
+            None,
+            false,
+            validate_project_name
+        )
+        .is_err());
+        assert_eq!(
+            ask_text(None, "dist", "", Some("build"), false, |_| Ok(())).unwrap(),
+            "build"
+        );
+    }
+}
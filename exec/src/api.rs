@@ -0,0 +1,481 @@
+use reqwest::{
+    blocking::{RequestBuilder, Response as HttpResponse},
+    StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    auth::{password, read_session, write_session, Session},
+    cache::TemplateIndex,
+    template::Template,
+    Cli, REQUEST,
+};
+
+/// The envelope of every response of the server
+#[derive(Debug, Deserialize)]
+pub(crate) struct Response<T> {
+    pub code: i32,
+    #[serde(default)]
+    pub msg: Option<String>,
+    pub data: Option<T>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct PageData<T> {
+    pub content: Vec<T>,
+    /// The number of the items on all the pages
+    #[serde(default, alias = "totalElements")]
+    pub total: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct Project {
+    pub id: i32,
+    pub name: String,
+    pub ssh_url: String,
+    pub http_url: String,
+    pub web_url: String,
+    pub build_cmd: String,
+    pub dist: String,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct NewProject<'a> {
+    pub name: &'a str,
+    pub build_cmd: &'a str,
+    pub dist: &'a str,
+    pub description: &'a str,
+}
+
+/// The fields of a project which can be changed after it is registered
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct ProjectPatch<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_cmd: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dist: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct NewTemplate<'a> {
+    pub name: &'a str,
+    pub repo: &'a str,
+    pub brief: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AuthBody {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    /// The lifetime of the access token in seconds
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct User {
+    pub email: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+/// The error of a request to the server
+#[derive(Debug)]
+pub(crate) enum ApiError {
+    /// The server can not be reached
+    Network(reqwest::Error),
+    /// The server rejects the request, with the code and the message of the envelope if any
+    Server {
+        status: StatusCode,
+        code: Option<i32>,
+        msg: Option<String>,
+    },
+    /// The response is not the expected one
+    Decode(String),
+    /// The session can not be read, saved or the credentials can not be prompted
+    Session(anyhow::Error),
+}
+
+impl ApiError {
+    /// whether it is a 400 for an expired token, the server answers some of them with 400
+    fn is_token_expired(&self) -> bool {
+        matches!(
+            self,
+            ApiError::Server {
+                status: StatusCode::BAD_REQUEST,
+                code: Some(TOKEN_EXPIRED),
+                ..
+            }
+        )
+    }
+
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ApiError::Server { status, .. } => Some(*status),
+            ApiError::Network(err) => err.status(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Network(err) => write!(f, "Failed to reach the server: {}", err),
+            ApiError::Server { status, code, msg } => {
+                write!(
+                    f,
+                    "{}",
+                    msg.as_deref()
+                        .unwrap_or_else(|| status.canonical_reason().unwrap_or("Unknown error"))
+                )?;
+                match code {
+                    Some(code) => write!(f, " (status {}, code {})", status.as_u16(), code),
+                    None => write!(f, " (status {})", status.as_u16()),
+                }
+            }
+            ApiError::Decode(msg) => write!(f, "Failed to parse the response: {}", msg),
+            ApiError::Session(err) => write!(f, "{:#}", err),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(err: reqwest::Error) -> ApiError {
+        if err.is_decode() {
+            ApiError::Decode(err.to_string())
+        } else {
+            ApiError::Network(err)
+        }
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+/// The code of the envelope when the server answers an expired token with 400
+const TOKEN_EXPIRED: i32 = 40100;
+
+/// The client of the yoo server, the requests are authorized with the cached session
+/// which is refreshed or renewed by logging in again when it is rejected
+pub(crate) struct YooApi<'a> {
+    cli: &'a Cli,
+    /// Whether to log in again when the session can't be refreshed, it may prompt the password
+    relogin: bool,
+}
+
+impl<'a> YooApi<'a> {
+    pub fn new(cli: &'a Cli) -> YooApi<'a> {
+        YooApi { cli, relogin: true }
+    }
+
+    /// a client which only refreshes the session, it never logs in again or prompts
+    pub fn without_relogin(cli: &'a Cli) -> YooApi<'a> {
+        YooApi {
+            cli,
+            relogin: false,
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.cli.server.as_deref().unwrap_or_default(), path)
+    }
+
+    pub fn login(&self, email: &str, password: &str) -> ApiResult<Session> {
+        let mut map = HashMap::new();
+        map.insert("email", email);
+        map.insert("password", password);
+
+        let resp = REQUEST
+            .post(self.url("/v1/users/login"))
+            .json(&map)
+            .send()?;
+        let body: AuthBody = decode(resp)?;
+
+        Ok(Session::new(body, Some(email.to_string())))
+    }
+
+    /// exchange the refresh token for a new access token
+    pub fn refresh(&self, session: &Session) -> ApiResult<Session> {
+        let refresh_token = session.refresh_token.as_deref().unwrap_or_default();
+
+        let mut map = HashMap::new();
+        map.insert("refresh_token", refresh_token);
+
+        let resp = REQUEST
+            .post(self.url("/v1/users/refresh"))
+            .json(&map)
+            .send()?;
+        let body: AuthBody = decode(resp)?;
+
+        Ok(Session::new(body, session.email.clone()))
+    }
+
+    /// revoke the tokens of the session
+    pub fn logout(&self, session: &Session) -> ApiResult<()> {
+        let mut map = HashMap::new();
+        if let Some(refresh_token) = &session.refresh_token {
+            map.insert("refresh_token", refresh_token.as_str());
+        }
+
+        let resp = REQUEST
+            .post(self.url("/v1/users/logout"))
+            .header("Authorization", &session.authorization)
+            .json(&map)
+            .send()?;
+        decode_empty(resp)
+    }
+
+    pub fn me(&self) -> ApiResult<User> {
+        self.send(|| REQUEST.get(self.url("/v1/users/me")))
+            .and_then(decode)
+    }
+
+    /// the templates with the validators of the response, None if the cached ones are not modified
+    pub fn list_templates(
+        &self,
+        cached: Option<&TemplateIndex>,
+    ) -> ApiResult<Option<TemplateIndex>> {
+        let mut request = REQUEST.get(self.url("/v1/templates"));
+        if let Some(index) = cached {
+            request = index.revalidate(request);
+        }
+
+        let resp = request.send()?;
+        if cached.is_some() && resp.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+
+        let index = TemplateIndex::from_response(&resp, vec![]);
+        let page: PageData<Template> = decode(resp)?;
+
+        Ok(Some(TemplateIndex {
+            templates: page.content,
+            ..index
+        }))
+    }
+
+    pub fn add_template(&self, template: &NewTemplate) -> ApiResult<Template> {
+        self.send(|| REQUEST.post(self.url("/v1/templates")).json(template))
+            .and_then(decode)
+    }
+
+    pub fn remove_template(&self, id: i32) -> ApiResult<()> {
+        self.send(|| REQUEST.delete(self.url(&format!("/v1/templates/{}", id))))
+            .and_then(decode_empty)
+    }
+
+    /// list a page of the projects, the pages start from 1
+    pub fn list_projects(
+        &self,
+        search: Option<&str>,
+        page: u32,
+        size: u32,
+    ) -> ApiResult<PageData<Project>> {
+        // the pages of the server start from 0
+        let mut query = vec![
+            ("page", page.saturating_sub(1).to_string()),
+            ("size", size.to_string()),
+        ];
+        if let Some(search) = search {
+            query.push(("name", search.to_string()));
+        }
+
+        self.send(|| REQUEST.get(self.url("/v1/projects")).query(&query))
+            .and_then(decode)
+    }
+
+    pub fn get_project(&self, id: i32) -> ApiResult<Project> {
+        self.send(|| REQUEST.get(self.url(&format!("/v1/projects/{}", id))))
+            .and_then(decode)
+    }
+
+    pub fn create_project(&self, project: &NewProject) -> ApiResult<Project> {
+        self.send(|| REQUEST.post(self.url("/v1/projects")).json(project))
+            .and_then(decode)
+    }
+
+    pub fn update_project(&self, id: i32, patch: &ProjectPatch) -> ApiResult<Project> {
+        self.send(|| {
+            REQUEST
+                .patch(self.url(&format!("/v1/projects/{}", id)))
+                .json(patch)
+        })
+        .and_then(decode)
+    }
+
+    pub fn delete_project(&self, id: i32) -> ApiResult<()> {
+        self.send(|| REQUEST.delete(self.url(&format!("/v1/projects/{}", id))))
+            .and_then(decode_empty)
+    }
+
+    /// the cached authorization, it is refreshed before it expires
+    pub fn authorization(&self) -> ApiResult<String> {
+        match read_session(self.cli).map_err(ApiError::Session)? {
+            Some(session) if !session.is_expiring() => Ok(session.authorization),
+            session => Ok(self.renew(session)?.authorization),
+        }
+    }
+
+    /// log in with the configured credentials, the password is prompted if it is not set or saved
+    pub fn relogin(&self) -> ApiResult<Session> {
+        let email = match self.cli.server_email.clone() {
+            Some(email) => email,
+            None => read_session(self.cli)
+                .map_err(ApiError::Session)?
+                .and_then(|session| session.email)
+                .ok_or_else(|| {
+                    ApiError::Session(anyhow::Error::msg(
+                        "SERVER_EMAIL is not set, run `yoo auth login` to log in",
+                    ))
+                })?,
+        };
+        let password = password(self.cli, &email).map_err(ApiError::Session)?;
+
+        let session = self.login(&email, &password)?;
+        write_session(self.cli, &session).map_err(ApiError::Session)?;
+        Ok(session)
+    }
+
+    // refresh the session, and log in again only when the refresh is rejected
+    fn renew(&self, session: Option<Session>) -> ApiResult<Session> {
+        if let Some(session) = session.filter(|x| x.refresh_token.is_some()) {
+            match self.refresh(&session) {
+                Ok(session) => {
+                    write_session(self.cli, &session).map_err(ApiError::Session)?;
+                    tracing::debug!("Refreshed the token");
+                    return Ok(session);
+                }
+                Err(err) => {
+                    tracing::debug!("Failed to refresh the token, logging in again: {}", err)
+                }
+            }
+        }
+
+        if !self.relogin {
+            return Err(ApiError::Session(anyhow::Error::msg(
+                "The session is expired and can't be refreshed",
+            )));
+        }
+        self.relogin()
+    }
+
+    // send the request with the authorization, it is sent again with a renewed one if the
+    // server rejects the authorization
+    fn send<F>(&self, request: F) -> ApiResult<HttpResponse>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        let resp = request()
+            .header("Authorization", self.authorization()?)
+            .send()?;
+
+        let status = resp.status();
+        if status == StatusCode::BAD_REQUEST {
+            // only the code tells an expired token from the other bad requests,
+            // which are returned as they are instead of being sent again
+            let err = server_error(status, &resp.text()?);
+            if !err.is_token_expired() {
+                return Err(err);
+            }
+        } else if status != StatusCode::UNAUTHORIZED {
+            return Ok(resp);
+        }
+
+        let session = self.renew(read_session(self.cli).map_err(ApiError::Session)?)?;
+        Ok(request()
+            .header("Authorization", session.authorization)
+            .send()?)
+    }
+}
+
+// unwrap the data of the envelope, a failed status or a non-zero code is an error
+fn decode<T: DeserializeOwned>(resp: HttpResponse) -> ApiResult<T> {
+    decode_envelope(resp)?
+        .ok_or_else(|| ApiError::Decode("There is no data in the response".to_string()))
+}
+
+fn decode_empty(resp: HttpResponse) -> ApiResult<()> {
+    decode_envelope::<serde_json::Value>(resp).map(|_| ())
+}
+
+fn decode_envelope<T: DeserializeOwned>(resp: HttpResponse) -> ApiResult<Option<T>> {
+    let status = resp.status();
+    let text = resp.text()?;
+
+    if !status.is_success() {
+        return Err(server_error(status, &text));
+    }
+
+    let envelope = serde_json::from_str::<Response<T>>(&text)
+        .map_err(|err| ApiError::Decode(err.to_string()))?;
+
+    if envelope.code != 0 {
+        return Err(ApiError::Server {
+            status,
+            code: Some(envelope.code),
+            msg: envelope.msg,
+        });
+    }
+
+    Ok(envelope.data)
+}
+
+fn server_error(status: StatusCode, text: &str) -> ApiError {
+    // the body of an error may not be an envelope at all
+    let envelope = serde_json::from_str::<Response<serde_json::Value>>(text).ok();
+    ApiError::Server {
+        status,
+        code: envelope.as_ref().map(|x| x.code),
+        msg: envelope.and_then(|x| x.msg),
+    }
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{server_error, ApiError};
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_server_error() {
+        let err = ApiError::Server {
+            status: StatusCode::BAD_REQUEST,
+            code: Some(40002),
+            msg: Some("project exists".to_string()),
+        };
+        assert_eq!(err.to_string(), "project exists (status 400, code 40002)");
+        assert_eq!(err.status(), Some(StatusCode::BAD_REQUEST));
+
+        let err = ApiError::Server {
+            status: StatusCode::BAD_GATEWAY,
+            code: None,
+            msg: None,
+        };
+        assert_eq!(err.to_string(), "Bad Gateway (status 502)");
+    }
+
+    #[test]
+    fn test_token_expired() {
+        // a plain bad request is returned as it is, without renewing the session and retrying
+        let err = server_error(
+            StatusCode::BAD_REQUEST,
+            r#"{"code": 40002, "msg": "project exists", "data": null}"#,
+        );
+        assert!(!err.is_token_expired());
+        assert_eq!(err.to_string(), "project exists (status 400, code 40002)");
+        assert!(!server_error(StatusCode::BAD_REQUEST, "Bad Request").is_token_expired());
+
+        assert!(server_error(
+            StatusCode::BAD_REQUEST,
+            r#"{"code": 40100, "msg": "token expired", "data": null}"#,
+        )
+        .is_token_expired());
+    }
+}
@@ -0,0 +1,440 @@
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use inquire::{Password, PasswordDisplayMode, Text};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    api::{ApiError, AuthBody, User, YooApi},
+    credentials,
+    loading::{loading, suspend},
+    output::emit,
+    profile::session_key,
+    Cli, CACHE_DIR, CACHE_FILE,
+};
+
+#[derive(Subcommand)]
+pub(crate) enum AuthCommands {
+    /// Log in to the server, the password is prompted unless YOO_SERVER_PASSWORD is set
+    Login {
+        /// The email to log in with, defaults to YOO_SERVER_EMAIL
+        #[arg(long)]
+        email: Option<String>,
+        /// Keep the password in the credential store to log in again when the session expires
+        #[arg(long)]
+        save_password: bool,
+    },
+    /// Revoke the cached token and remove it with the saved password
+    Logout,
+    /// Show the current user and when the token expires
+    #[command(visible_alias = "whoami")]
+    Status,
+    /// Print the authorization header, e.g. `curl -H "Authorization: $(yoo auth token)"`
+    Token,
+}
+
+pub(crate) fn auth(cli: &Cli, command: AuthCommands) -> Result<()> {
+    match command {
+        AuthCommands::Login {
+            email,
+            save_password,
+        } => {
+            let email = match email.or_else(|| cli.server_email.clone()) {
+                Some(email) => email,
+                None => Text::new("Please enter your email:")
+                    .prompt()
+                    .with_context(|| "Failed to interact with the user")?,
+            };
+            let password = match &cli.server_password {
+                Some(password) => password.clone(),
+                None => prompt_password()?,
+            };
+
+            let pb = loading("Logging in")?;
+            let session = YooApi::new(cli).login(&email, &password)?;
+            pb.finish_and_clear();
+            write_session(cli, &session)?;
+
+            if save_password {
+                credentials::store()?.set(&password_key(cli, &email), &password)?;
+                tracing::info!("Successfully saved the password");
+            }
+
+            tracing::info!("Successfully logged in as {}", email);
+            emit(
+                cli.output,
+                &LoginOutput {
+                    email: &email,
+                    server: cli.server.as_deref().unwrap_or_default(),
+                    profile: cli.profile.as_deref(),
+                    expires_at: session.expires_at,
+                    password_saved: save_password,
+                },
+                || Ok(()),
+            )
+        }
+        AuthCommands::Logout => logout(cli),
+        AuthCommands::Status => status(cli),
+        AuthCommands::Token => {
+            let authorization = YooApi::new(cli).authorization()?;
+            emit(
+                cli.output,
+                &serde_json::json!({ "authorization": authorization }),
+                || {
+                    println!("{}", authorization);
+                    Ok(())
+                },
+            )
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct LoginOutput<'a> {
+    email: &'a str,
+    server: &'a str,
+    profile: Option<&'a str>,
+    expires_at: Option<u64>,
+    password_saved: bool,
+}
+
+/// The login state reported by `yoo auth status`
+#[derive(Serialize)]
+struct StatusOutput<'a> {
+    logged_in: bool,
+    server: &'a str,
+    profile: Option<&'a str>,
+    user: Option<User>,
+    /// Whether the server rejects the cached session
+    rejected: bool,
+    /// The unix time when the access token expires
+    expires_at: Option<u64>,
+    store: String,
+}
+
+// the token is refreshed when it expires within the margin
+const REFRESH_MARGIN: u64 = 60;
+
+// the key of the tokens before they were kept per profile and server
+const LEGACY_SESSION_KEY: &str = "session";
+
+/// The login state kept in the credential store
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct Session {
+    pub authorization: String,
+    pub refresh_token: Option<String>,
+    pub email: Option<String>,
+    /// The unix time when the access token expires
+    pub expires_at: Option<u64>,
+}
+
+impl Session {
+    pub fn new(body: AuthBody, email: Option<String>) -> Session {
+        Session {
+            authorization: format!("{} {}", body.token_type, body.access_token),
+            refresh_token: Some(body.refresh_token),
+            email,
+            expires_at: body.expires_in.map(|x| now() + x),
+        }
+    }
+
+    /// whether the access token is expired or about to expire
+    pub fn is_expiring(&self) -> bool {
+        self.expires_at
+            .map(|expires_at| expires_at <= now() + REFRESH_MARGIN)
+            .unwrap_or(false)
+    }
+}
+
+/// the password of the configuration or the saved one, or prompt for it
+pub(crate) fn password(cli: &Cli, email: &str) -> Result<String> {
+    if let Some(password) = &cli.server_password {
+        return Ok(password.clone());
+    }
+
+    match credentials::store()?.get(&password_key(cli, email))? {
+        Some(password) => Ok(password),
+        None => prompt_password(),
+    }
+}
+
+// logging in again may happen under the spinner of a request
+fn prompt_password() -> Result<String> {
+    suspend(|| {
+        Password::new("Please enter your password:")
+            .without_confirmation()
+            .with_display_mode(PasswordDisplayMode::Masked)
+            .prompt()
+    })
+    .with_context(|| "Failed to interact with the user")
+}
+
+fn password_key(cli: &Cli, email: &str) -> String {
+    format!(
+        "password:{}@{}",
+        email,
+        cli.server.as_deref().unwrap_or_default()
+    )
+}
+
+fn logout(cli: &Cli) -> Result<()> {
+    let session = match read_session(cli)? {
+        Some(session) => session,
+        None => {
+            tracing::info!("You are not logged in");
+            return emit(
+                cli.output,
+                &serde_json::json!({ "logged_out": false, "revoked": false }),
+                || Ok(()),
+            );
+        }
+    };
+
+    // the cached token is removed even if the server fails to revoke it
+    let revoked = match YooApi::new(cli).logout(&session) {
+        Ok(_) => true,
+        Err(err) => {
+            tracing::warn!("Failed to revoke the token: {}", err);
+            false
+        }
+    };
+
+    clear_session(cli)?;
+    if let Some(email) = &session.email {
+        credentials::store()?.delete(&password_key(cli, email))?;
+    }
+    tracing::info!("Successfully logged out");
+    emit(
+        cli.output,
+        &serde_json::json!({ "logged_out": true, "revoked": revoked }),
+        || Ok(()),
+    )
+}
+
+fn status(cli: &Cli) -> Result<()> {
+    let mut output = StatusOutput {
+        logged_in: false,
+        server: cli.server.as_deref().unwrap_or_default(),
+        profile: cli.profile.as_deref(),
+        user: None,
+        rejected: false,
+        expires_at: None,
+        store: credentials::store()?.name(),
+    };
+
+    let session = match read_session(cli)? {
+        Some(session) => session,
+        None => {
+            return emit(cli.output, &output, || {
+                println!("You are not logged in, run `yoo auth login` to log in");
+                Ok(())
+            })
+        }
+    };
+
+    let pb = loading("Fetching the user")?;
+    // a status never logs in again, it would prompt the password in a script
+    let user = YooApi::without_relogin(cli).me();
+    pb.finish_and_clear();
+
+    let rejected = match &user {
+        Ok(_) => false,
+        Err(ApiError::Session(err)) => {
+            tracing::debug!("{:#}", err);
+            true
+        }
+        Err(err) => err.status() == Some(StatusCode::UNAUTHORIZED),
+    };
+    match user {
+        Ok(user) => {
+            output.logged_in = true;
+            output.user = Some(user);
+        }
+        Err(_) if rejected => {
+            output.rejected = true;
+            return emit(cli.output, &output, || {
+                println!(
+                    "The session of {} is expired or rejected, run `yoo auth login` to log in again",
+                    session.email.as_deref().unwrap_or("the cached user")
+                );
+                Ok(())
+            });
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    // the session may be refreshed by the request
+    output.expires_at = read_session(cli)?.unwrap_or(session).expires_at;
+
+    emit(cli.output, &output, || {
+        match &output.user {
+            Some(user) if user.name.is_empty() => println!(
+                "Logged in to {}{} as {}",
+                output.server,
+                profile_suffix(cli),
+                user.email
+            ),
+            Some(user) => println!(
+                "Logged in to {}{} as {} <{}>",
+                output.server,
+                profile_suffix(cli),
+                user.name,
+                user.email
+            ),
+            None => {}
+        }
+        match output.expires_at {
+            Some(expires_at) if expires_at > now() => println!(
+                "The token expires in {}",
+                format_duration(expires_at - now())
+            ),
+            Some(_) => println!("The token is expired"),
+            None => println!("The expiry of the token is unknown"),
+        }
+        println!("The credentials are kept in the {}", output.store);
+        Ok(())
+    })
+}
+
+fn profile_suffix(cli: &Cli) -> String {
+    match &cli.profile {
+        Some(profile) => format!(" (profile {})", profile),
+        None => String::new(),
+    }
+}
+
+fn cache_file() -> Result<PathBuf> {
+    let home_dir = dirs::home_dir().with_context(|| "Failed to get the home dir")?;
+    Ok(home_dir.join(format!("{}/{}", CACHE_DIR, CACHE_FILE)))
+}
+
+fn read_cache() -> Result<HashMap<String, String>> {
+    let cache_file = cache_file()?;
+    if !cache_file.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let file = File::open(cache_file).with_context(|| "Failed to open the cache file")?;
+    if file.metadata()?.len() == 0 {
+        return Ok(HashMap::new());
+    }
+
+    serde_json::from_reader(BufReader::new(file)).with_context(|| "Failed to parse the cache file")
+}
+
+fn write_cache(cache: &HashMap<String, String>) -> Result<()> {
+    let file = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .open(cache_file()?)
+        .with_context(|| "Failed to write the cache file")?;
+
+    serde_json::to_writer_pretty(BufWriter::new(file), cache)
+        .with_context(|| "Failed to write the cache file")
+}
+
+/// the session of the profile on the server
+pub(crate) fn read_session(cli: &Cli) -> Result<Option<Session>> {
+    match credentials::store()?.get(&cli_session_key(cli))? {
+        Some(session) => serde_json::from_str(&session)
+            .map(Some)
+            .with_context(|| "Failed to parse the session"),
+        None => migrate_session(cli),
+    }
+}
+
+pub(crate) fn write_session(cli: &Cli, session: &Session) -> Result<()> {
+    credentials::store()?.set(&cli_session_key(cli), &serde_json::to_string(session)?)
+}
+
+fn clear_session(cli: &Cli) -> Result<()> {
+    credentials::store()?.delete(&cli_session_key(cli))
+}
+
+fn cli_session_key(cli: &Cli) -> String {
+    session_key(
+        cli.profile.as_deref(),
+        cli.server.as_deref().unwrap_or_default(),
+    )
+}
+
+// the tokens were kept in plaintext in the cache file and then under a single key,
+// move them to the key of the current profile
+fn migrate_session(cli: &Cli) -> Result<Option<Session>> {
+    let store = credentials::store()?;
+    if let Some(session) = store.get(LEGACY_SESSION_KEY)? {
+        let session: Session =
+            serde_json::from_str(&session).with_context(|| "Failed to parse the session")?;
+        write_session(cli, &session)?;
+        store.delete(LEGACY_SESSION_KEY)?;
+        return Ok(Some(session));
+    }
+
+    let mut cache = read_cache()?;
+    let session = match cache.remove("authorization") {
+        Some(authorization) => Session {
+            authorization,
+            refresh_token: cache.remove("refresh_token"),
+            email: cache.remove("email"),
+            expires_at: cache.remove("expires_at").and_then(|x| x.parse().ok()),
+        },
+        None => return Ok(None),
+    };
+
+    write_session(cli, &session)?;
+    write_cache(&cache)?;
+    tracing::info!("Moved the cached token to the credential store");
+
+    Ok(Some(session))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default()
+}
+
+fn format_duration(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{format_duration, now, Session};
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(42), "42s");
+        assert_eq!(format_duration(600), "10m");
+        assert_eq!(format_duration(3600 * 2 + 60 * 5 + 7), "2h 5m");
+    }
+
+    #[test]
+    fn test_is_expiring() {
+        let session = |expires_at| Session {
+            authorization: "Bearer token".to_string(),
+            refresh_token: None,
+            email: None,
+            expires_at,
+        };
+
+        assert!(!session(None).is_expiring());
+        assert!(!session(Some(now() + 3600)).is_expiring());
+        assert!(session(Some(now() + 10)).is_expiring());
+        assert!(session(Some(now() - 10)).is_expiring());
+    }
+}
@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+use crate::{template::Template, CACHE_DIR, TEMPLATES_DIR};
+
+const INDEX_FILE: &str = "index.json";
+const MIRRORS_DIR: &str = "mirrors";
+
+/// The cached response of `/v1/templates`, with the validators to revalidate it
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct TemplateIndex {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub templates: Vec<Template>,
+}
+
+impl TemplateIndex {
+    pub fn load() -> Result<Option<TemplateIndex>> {
+        let index_file = templates_dir()?.join(INDEX_FILE);
+        if !index_file.exists() {
+            return Ok(None);
+        }
+
+        let file = File::open(index_file).with_context(|| "Failed to open the template cache")?;
+        let index = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| "Failed to parse the template cache")?;
+
+        Ok(Some(index))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let dir = templates_dir()?;
+        fs::create_dir_all(&dir).with_context(|| "Failed to create the template cache dir")?;
+
+        let file = File::create(dir.join(INDEX_FILE))
+            .with_context(|| "Failed to write the template cache")?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)
+            .with_context(|| "Failed to write the template cache")?;
+        Ok(())
+    }
+
+    /// add the conditional headers to the request
+    pub fn revalidate(
+        &self,
+        mut request: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+
+    /// a new index with the validators of the response
+    pub fn from_response(
+        resp: &reqwest::blocking::Response,
+        templates: Vec<Template>,
+    ) -> TemplateIndex {
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+
+        TemplateIndex {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            templates,
+        }
+    }
+}
+
+/// the bare mirror of the template repo, it is cloned or fetched unless offline
+pub(crate) fn mirror(repo: &str, offline: bool, options: &git::RemoteOptions) -> Result<PathBuf> {
+    let mirror_dir = templates_dir()?.join(MIRRORS_DIR).join(mirror_name(repo));
+    let mirror_path = mirror_dir
+        .to_str()
+        .with_context(|| "Failed to convert the path to string")?;
+
+    if mirror_dir.exists() {
+        if offline {
+            tracing::debug!("Using the cached mirror of {}", repo);
+        } else if let Err(err) = git::update_mirror(mirror_path, options) {
+            tracing::warn!(
+                "Failed to update the template, using the cached one: {}",
+                err
+            );
+        }
+        return Ok(mirror_dir);
+    }
+
+    if offline {
+        return Err(anyhow::Error::msg(format!(
+            "The template {} is not cached, run the command without --offline first",
+            repo
+        )));
+    }
+
+    fs::create_dir_all(templates_dir()?.join(MIRRORS_DIR))
+        .with_context(|| "Failed to create the template cache dir")?;
+    git::clone_mirror(repo, mirror_path, options)?;
+
+    Ok(mirror_dir)
+}
+
+fn templates_dir() -> Result<PathBuf> {
+    let home_dir = dirs::home_dir().with_context(|| "Failed to get the home dir")?;
+    Ok(home_dir.join(CACHE_DIR).join(TEMPLATES_DIR))
+}
+
+// a readable directory name for the repo url
+fn mirror_name(repo: &str) -> String {
+    let name: String = repo
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.git", name)
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::mirror_name;
+
+    #[test]
+    fn test_mirror_name() {
+        assert_eq!(
+            mirror_name("ssh://git@192.168.31.162:222/yoo/react.git"),
+            "ssh___git_192_168_31_162_222_yoo_react.git"
+        );
+        assert_eq!(
+            mirror_name("git@gitlab.com:yoo/vue"),
+            "git_gitlab_com_yoo_vue.git"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use std::{env, path::PathBuf};
+
+use crate::{
+    loading::loading,
+    metadata::{Metadata, ProjectInfo, METADATA_FILE},
+    output::emit,
+    project::resolve_project,
+    remote::remote_options,
+    Cli,
+};
+
+#[derive(Args)]
+pub(crate) struct CloneArgs {
+    /// The project to clone
+    #[arg(value_name = "ID|NAME")]
+    project: String,
+
+    /// The directory to clone into, defaults to the project name
+    dir: Option<PathBuf>,
+
+    /// Clone through the http url instead of the ssh url
+    #[arg(long)]
+    https: bool,
+}
+
+// clone the project registered on the server and check out its dev branch
+pub(crate) fn clone(cli: &Cli, args: CloneArgs) -> Result<()> {
+    let project = resolve_project(cli, Some(&args.project))?;
+
+    let project_dir = env::current_dir()
+        .with_context(|| "Failed to get the current directory")?
+        .join(args.dir.unwrap_or_else(|| PathBuf::from(&project.name)));
+    if project_dir.exists()
+        && project_dir
+            .read_dir()
+            .with_context(|| "Failed to read the project directory")?
+            .next()
+            .is_some()
+    {
+        return Err(anyhow::Error::msg(format!(
+            "The directory {} is not empty",
+            project_dir.display()
+        )));
+    }
+    let project_path = project_dir
+        .to_str()
+        .with_context(|| "Failed to convert the path to string")?;
+
+    let url = if args.https {
+        &project.http_url
+    } else {
+        &project.ssh_url
+    };
+
+    let pb = loading("Cloning")?;
+    let git_repo = git::clone(
+        url,
+        project_path,
+        &remote_options(cli).with_progress(pb.transfer()),
+    )?;
+    pb.finish_and_clear();
+    tracing::info!(
+        "Successfully cloned the project {} from {}",
+        project.name,
+        url
+    );
+
+    let branch = match git_repo.checkout_remote_branch("dev") {
+        Ok(_) => {
+            tracing::info!("Successfully checked out to dev branch");
+            Some("dev")
+        }
+        Err(err) => {
+            tracing::warn!("Failed to check out the dev branch: {}", err);
+            None
+        }
+    };
+
+    // the metadata tracked by the remote is kept as it is, otherwise it is written
+    // but left uncommitted, so nothing is committed on behalf of the user
+    let written = match Metadata::load(&project_dir)? {
+        Some(metadata) => {
+            if metadata.project.as_ref().map(|x| x.id) != Some(project.id) {
+                tracing::warn!(
+                    "The {} of the repo doesn't point at the project {}, it is left as it is",
+                    METADATA_FILE,
+                    project.name
+                );
+            }
+            false
+        }
+        None => {
+            let metadata = Metadata {
+                project: Some(ProjectInfo::new(&project, cli.server.as_ref().unwrap())),
+                ..Default::default()
+            };
+            metadata.save(&project_dir)?;
+            tracing::info!(
+                "Successfully wrote the {}, commit and push it to share it with your team",
+                METADATA_FILE
+            );
+            true
+        }
+    };
+
+    let output = serde_json::json!({
+        "project": project,
+        "dir": project_path,
+        "url": url,
+        "branch": branch,
+        "metadata_written": written,
+    });
+    emit(cli.output, &output, || Ok(()))
+}
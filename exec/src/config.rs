@@ -0,0 +1,577 @@
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use std::{collections::BTreeMap, env, fmt, fs, path::PathBuf, process::Command};
+use toml_edit::{Document, Item, TableLike, Value};
+
+use crate::{
+    metadata::{Metadata, METADATA_FILE},
+    output::emit,
+    profile::Profiles,
+    Cli, CACHE_DIR,
+};
+
+pub const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Subcommand)]
+pub(crate) enum ConfigCommands {
+    /// Print the effective value of a setting
+    Get { key: String },
+    /// Set a setting in the config file of the user or the project
+    Set {
+        key: String,
+        value: String,
+        /// Write it to the [config] of the yoo.toml of the current project
+        #[arg(long)]
+        project: bool,
+    },
+    /// Remove a setting from the config file of the user or the project
+    Unset {
+        key: String,
+        /// Remove it from the [config] of the yoo.toml of the current project
+        #[arg(long)]
+        project: bool,
+    },
+    /// List the effective settings and where they come from
+    List,
+    /// Open the config file in $VISUAL or $EDITOR
+    Edit {
+        /// Edit the yoo.toml of the current project instead
+        #[arg(long)]
+        project: bool,
+    },
+    /// Show every value of the settings in the order of precedence, the effective one is marked with *
+    ///
+    /// The precedence from the highest to the lowest: the flags, the profile given by
+    /// `--profile` or YOO_PROFILE, the env vars, the [config] of the yoo.toml of the project,
+    /// the profile given in a config file and ~/.yoo/config.toml
+    Explain { key: Option<String> },
+}
+
+/// A setting which can be configured in the config files
+struct Setting {
+    key: &'static str,
+    flag: Option<&'static str>,
+    env: Option<&'static str>,
+}
+
+// the password is not a setting, it is kept in the env or the credential store
+const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "server",
+        flag: Some("--server"),
+        env: Some("YOO_SERVER"),
+    },
+    Setting {
+        key: "email",
+        flag: Some("--server-email"),
+        env: Some("YOO_SERVER_EMAIL"),
+    },
+    Setting {
+        key: "profile",
+        flag: Some("--profile"),
+        env: Some("YOO_PROFILE"),
+    },
+    Setting {
+        key: "gitlab.server",
+        flag: None,
+        env: Some("YOO_GITLAB_SERVER"),
+    },
+    Setting {
+        key: "git.ssh_key",
+        flag: None,
+        env: Some("YOO_SSH_KEY"),
+    },
+    Setting {
+        key: "git.fallback",
+        flag: None,
+        env: Some("YOO_GIT_FALLBACK"),
+    },
+];
+
+/// Where a value comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Source {
+    Flag,
+    Env,
+    Profile(String),
+    Project(PathBuf),
+    User(PathBuf),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Flag => write!(f, "flag"),
+            Source::Env => write!(f, "env"),
+            Source::Profile(name) => write!(f, "profile {}", name),
+            Source::Project(path) => write!(f, "project {}", path.display()),
+            Source::User(path) => write!(f, "user {}", path.display()),
+        }
+    }
+}
+
+/// The values of a source, the keys are dotted, e.g. `gitlab.server`
+#[derive(Debug, Clone)]
+struct Layer {
+    source: Source,
+    values: BTreeMap<String, String>,
+}
+
+/// The settings merged from the flags, the env and the config files
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Config {
+    /// The layers from the highest precedence to the lowest
+    layers: Vec<Layer>,
+}
+
+impl Config {
+    pub fn resolve(cli: &Cli) -> Result<Config> {
+        let flags = [
+            ("server", &cli.server),
+            ("email", &cli.server_email),
+            ("profile", &cli.profile),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.clone().map(|value| (key.to_string(), value)))
+        .collect();
+
+        let env = SETTINGS
+            .iter()
+            .filter_map(|setting| {
+                let value = env::var(setting.env?).ok()?;
+                Some((setting.key.to_string(), value))
+            })
+            .collect();
+
+        let mut layers = vec![
+            Layer {
+                source: Source::Flag,
+                values: flags,
+            },
+            Layer {
+                source: Source::Env,
+                values: env,
+            },
+        ];
+
+        if let Some((root, metadata)) = Metadata::discover()? {
+            layers.push(Layer {
+                source: Source::Project(root.join(METADATA_FILE)),
+                values: flatten(&metadata.config.unwrap_or_default()),
+            });
+        }
+
+        let mut user = read_config()?;
+        user.remove("profiles");
+        layers.push(Layer {
+            source: Source::User(config_file()?),
+            values: flatten(&user),
+        });
+
+        // the profile goes under the flags when it is given explicitly, or above the user config
+        let mut config = Config { layers };
+        if let Some((name, source)) = config.get_with_source("profile") {
+            let explicit = matches!(source, Source::Flag | Source::Env);
+            let name = name.to_string();
+            let profile = Profiles::load()?.get(&name)?.clone();
+
+            let mut values = BTreeMap::from([("server".to_string(), profile.server)]);
+            if let Some(email) = profile.email {
+                values.insert("email".to_string(), email);
+            }
+
+            let index = if explicit { 1 } else { config.layers.len() - 1 };
+            config.layers.insert(
+                index,
+                Layer {
+                    source: Source::Profile(name),
+                    values,
+                },
+            );
+        }
+
+        Ok(config)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_with_source(key).map(|(value, _)| value)
+    }
+
+    fn get_with_source(&self, key: &str) -> Option<(&str, &Source)> {
+        self.layers
+            .iter()
+            .find_map(|layer| Some((layer.values.get(key)?.as_str(), &layer.source)))
+    }
+
+    // every value of the key from the highest precedence to the lowest
+    fn explain(&self, key: &str) -> Vec<(&str, &Source)> {
+        self.layers
+            .iter()
+            .filter_map(|layer| Some((layer.values.get(key)?.as_str(), &layer.source)))
+            .collect()
+    }
+}
+
+pub(crate) fn config(cli: &Cli, command: ConfigCommands) -> Result<()> {
+    match command {
+        ConfigCommands::Get { key } => {
+            let setting = find_setting(&key)?;
+            let config = Config::resolve(cli)?;
+            let (value, source) = config
+                .get_with_source(setting.key)
+                .with_context(|| format!("{} is not set", key))?;
+            let output = serde_json::json!({
+                "key": setting.key,
+                "value": value,
+                "source": describe(setting, source),
+            });
+            emit(cli.output, &output, || {
+                println!("{}", value);
+                Ok(())
+            })
+        }
+        ConfigCommands::Set {
+            key,
+            value,
+            project,
+        } => {
+            let setting = find_setting(&key)?;
+            if setting.key == "profile" {
+                Profiles::load()?.get(&value)?;
+            }
+            edit_config(project, |table| set_value(table, setting.key, &value))?;
+            tracing::info!("Successfully set {} to {}", setting.key, value);
+            let output = serde_json::json!({ "key": setting.key, "value": value });
+            emit(cli.output, &output, || Ok(()))
+        }
+        ConfigCommands::Unset { key, project } => {
+            let setting = find_setting(&key)?;
+            edit_config(project, |table| unset_value(table, setting.key))?;
+            tracing::info!("Successfully unset {}", setting.key);
+            let output = serde_json::json!({ "key": setting.key, "value": null });
+            emit(cli.output, &output, || Ok(()))
+        }
+        ConfigCommands::List => {
+            let config = Config::resolve(cli)?;
+            let values: Vec<_> = SETTINGS
+                .iter()
+                .filter_map(|setting| {
+                    config
+                        .get_with_source(setting.key)
+                        .map(|(value, source)| (setting, value, describe(setting, source)))
+                })
+                .collect();
+            let output: Vec<_> = values
+                .iter()
+                .map(|(setting, value, source)| {
+                    serde_json::json!({ "key": setting.key, "value": value, "source": source })
+                })
+                .collect();
+            emit(cli.output, &output, || {
+                for (setting, value, source) in &values {
+                    println!("{} = {}  ({})", setting.key, value, source);
+                }
+                Ok(())
+            })
+        }
+        ConfigCommands::Edit { project } => {
+            let path = open_editor(project)?;
+            emit(cli.output, &serde_json::json!({ "file": path }), || Ok(()))
+        }
+        ConfigCommands::Explain { key } => {
+            let settings = match key {
+                Some(key) => vec![find_setting(&key)?],
+                None => SETTINGS.iter().collect(),
+            };
+
+            let config = Config::resolve(cli)?;
+            let explained: Vec<_> = settings
+                .into_iter()
+                .map(|setting| {
+                    let values: Vec<_> = config
+                        .explain(setting.key)
+                        .into_iter()
+                        .map(|(value, source)| (value, describe(setting, source)))
+                        .collect();
+                    (setting, values)
+                })
+                .collect();
+            let output: Vec<_> = explained
+                .iter()
+                .map(|(setting, values)| {
+                    let values: Vec<_> = values
+                        .iter()
+                        .enumerate()
+                        .map(|(i, (value, source))| {
+                            serde_json::json!({
+                                "value": value,
+                                "source": source,
+                                "effective": i == 0,
+                            })
+                        })
+                        .collect();
+                    serde_json::json!({ "key": setting.key, "values": values })
+                })
+                .collect();
+
+            emit(cli.output, &output, || {
+                for (setting, values) in &explained {
+                    if values.is_empty() {
+                        println!("{} is not set", setting.key);
+                        continue;
+                    }
+
+                    println!("{}:", setting.key);
+                    for (i, (value, source)) in values.iter().enumerate() {
+                        println!(
+                            "  {} {}  ({})",
+                            if i == 0 { "*" } else { " " },
+                            value,
+                            source
+                        );
+                    }
+                }
+                Ok(())
+            })
+        }
+    }
+}
+
+pub(crate) fn config_file() -> Result<PathBuf> {
+    let home_dir = dirs::home_dir().with_context(|| "Failed to get the home dir")?;
+    Ok(home_dir.join(CACHE_DIR).join(CONFIG_FILE))
+}
+
+fn read_config() -> Result<toml::Table> {
+    let path = config_file()?;
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read the {}", path.display()))?;
+    toml::from_str(&content).with_context(|| format!("Failed to parse the {}", path.display()))
+}
+
+// change the user config, or the [config] of the yoo.toml of the project,
+// the document is edited in place so that the comments and the order of the keys are kept
+fn edit_config<F>(project: bool, edit: F) -> Result<()>
+where
+    F: FnOnce(&mut dyn TableLike),
+{
+    let path = if project {
+        Metadata::discover()?
+            .map(|(root, _)| root.join(METADATA_FILE))
+            .with_context(|| {
+                format!(
+                    "There is no {} in the current directory or its parents",
+                    METADATA_FILE
+                )
+            })?
+    } else {
+        config_file()?
+    };
+
+    let content = if path.exists() {
+        fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read the {}", path.display()))?
+    } else {
+        String::new()
+    };
+    let mut document = content
+        .parse::<Document>()
+        .with_context(|| format!("Failed to parse the {}", path.display()))?;
+
+    if project {
+        let config = document
+            .entry("config")
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .with_context(|| format!("The [config] of the {} is not a table", METADATA_FILE))?;
+        edit(config);
+        if config.is_empty() {
+            document.remove("config");
+        }
+    } else {
+        edit(document.as_table_mut());
+    }
+
+    fs::write(&path, document.to_string())
+        .with_context(|| format!("Failed to write the {}", path.display()))
+}
+
+// open the config file in the editor of the user, the path of the file is returned
+fn open_editor(project: bool) -> Result<PathBuf> {
+    let path = if project {
+        Metadata::discover()?
+            .map(|(root, _)| root.join(METADATA_FILE))
+            .with_context(|| {
+                format!(
+                    "There is no {} in the current directory or its parents",
+                    METADATA_FILE
+                )
+            })?
+    } else {
+        config_file()?
+    };
+
+    let default_editor = if cfg!(windows) { "notepad" } else { "vi" };
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| default_editor.to_string());
+
+    let status = Command::new(&editor)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to run the editor {}", editor))?;
+    if !status.success() {
+        return Err(anyhow::Error::msg(format!("The editor {} failed", editor)));
+    }
+
+    // the file is checked so that the next command doesn't fail on it
+    let content = fs::read_to_string(&path).unwrap_or_default();
+    if let Err(err) = toml::from_str::<toml::Table>(&content) {
+        tracing::warn!("The {} is not valid: {}", path.display(), err);
+    }
+    Ok(path)
+}
+
+fn find_setting(key: &str) -> Result<&'static Setting> {
+    SETTINGS.iter().find(|x| x.key == key).with_context(|| {
+        let keys: Vec<&str> = SETTINGS.iter().map(|x| x.key).collect();
+        format!(
+            "Unknown setting {}, the settings are {}",
+            key,
+            keys.join(", ")
+        )
+    })
+}
+
+fn describe(setting: &Setting, source: &Source) -> String {
+    match (source, setting.flag, setting.env) {
+        (Source::Flag, Some(flag), _) => format!("flag {}", flag),
+        (Source::Env, _, Some(env)) => format!("env {}", env),
+        _ => source.to_string(),
+    }
+}
+
+// the nested tables as dotted keys, the values which are not strings are kept as toml
+fn flatten(table: &toml::Table) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    for (key, value) in table {
+        match value {
+            toml::Value::Table(table) => {
+                for (sub_key, value) in flatten(table) {
+                    values.insert(format!("{}.{}", key, sub_key), value);
+                }
+            }
+            toml::Value::String(value) => {
+                values.insert(key.clone(), value.clone());
+            }
+            value => {
+                values.insert(key.clone(), value.to_string());
+            }
+        }
+    }
+    values
+}
+
+fn set_value(table: &mut dyn TableLike, key: &str, value: &str) {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            let entry = table.entry(head).or_insert_with(toml_edit::table);
+            if !entry.is_table_like() {
+                *entry = toml_edit::table();
+            }
+            if let Some(sub_table) = entry.as_table_like_mut() {
+                set_value(sub_table, rest, value);
+            }
+        }
+        None => match table.get_mut(key) {
+            // the comment after the old value is kept
+            Some(Item::Value(old)) => {
+                let decor = old.decor().clone();
+                *old = Value::from(value);
+                *old.decor_mut() = decor;
+            }
+            _ => {
+                table.insert(key, toml_edit::value(value));
+            }
+        },
+    }
+}
+
+fn unset_value(table: &mut dyn TableLike, key: &str) {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            if let Some(sub_table) = table.get_mut(head).and_then(Item::as_table_like_mut) {
+                unset_value(sub_table, rest);
+                if sub_table.is_empty() {
+                    table.remove(head);
+                }
+            }
+        }
+        None => {
+            table.remove(key);
+        }
+    }
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{flatten, set_value, unset_value, Config, Layer, Source};
+    use std::{collections::BTreeMap, path::PathBuf};
+    use toml_edit::Document;
+
+    #[test]
+    fn test_set_and_unset_value() {
+        let mut document = Document::new();
+        set_value(document.as_table_mut(), "server", "https://yoo");
+        set_value(document.as_table_mut(), "gitlab.server", "https://gitlab");
+
+        let values = flatten(&toml::from_str(&document.to_string()).unwrap());
+        assert_eq!(values["server"], "https://yoo");
+        assert_eq!(values["gitlab.server"], "https://gitlab");
+
+        unset_value(document.as_table_mut(), "gitlab.server");
+        assert!(!document.contains_key("gitlab"));
+    }
+
+    #[test]
+    fn test_set_keeps_comments() {
+        let content =
+            "# the server of the team\nserver = \"https://yoo\" # prod\nemail = \"a@yoo\"\n";
+        let mut document = content.parse::<Document>().unwrap();
+        set_value(document.as_table_mut(), "server", "https://dev.yoo");
+        assert_eq!(
+            document.to_string(),
+            "# the server of the team\nserver = \"https://dev.yoo\" # prod\nemail = \"a@yoo\"\n"
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        let layer = |source, values: &[(&str, &str)]| Layer {
+            source,
+            values: values
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<BTreeMap<_, _>>(),
+        };
+        let config = Config {
+            layers: vec![
+                layer(Source::Env, &[("email", "env@yoo")]),
+                layer(
+                    Source::User(PathBuf::from("config.toml")),
+                    &[("email", "user@yoo"), ("server", "https://yoo")],
+                ),
+            ],
+        };
+
+        assert_eq!(config.get("email"), Some("env@yoo"));
+        assert_eq!(config.get("server"), Some("https://yoo"));
+        assert_eq!(config.explain("email").len(), 2);
+        assert_eq!(config.get("profile"), None);
+    }
+}
@@ -1,164 +1,249 @@
 use anyhow::{Context, Result};
+use clap::Args;
 use console::{style, Emoji};
-use inquire::{validator::Validation, Confirm, Select, Text};
-use regex::Regex;
-use reqwest::StatusCode;
-use serde::{Deserialize, Serialize};
+use inquire::Select;
 use std::{
-    collections::HashMap,
     env,
-    fs::{remove_dir_all, remove_file, DirEntry, File, OpenOptions},
-    io::{BufReader, BufWriter, Error},
+    fs::{remove_dir_all, remove_file, rename},
+    path::{Path, PathBuf},
 };
 
-use crate::{loading::loading, Cli, CACHE_DIR, CACHE_FILE, REQUEST};
+use crate::{
+    answers::{ask_confirm, ask_text, validate_project_name, Answers},
+    api::{NewProject, YooApi},
+    journal::{Journal, Step},
+    loading::loading,
+    manifest::{Manifest, RenderContext},
+    metadata::{Metadata, ProjectInfo, TemplateProvenance},
+    output::emit,
+    remote::remote_options,
+    template::{checkout_template, fetch_templates, Template, TemplateKind, TemplateSpec},
+    Cli,
+};
 
-#[derive(Debug, Deserialize)]
-struct Response<T> {
-    code: i32,
-    // msg: String,
-    data: T,
-}
+#[derive(Args)]
+pub(crate) struct CreateArgs {
+    #[command(flatten)]
+    answers: Answers,
 
-#[derive(Debug, Deserialize)]
-struct PageData<T> {
-    content: Vec<T>,
-}
+    /// Read the answers from a toml or json file, the flags take precedence over it
+    #[arg(long = "answers", value_name = "FILE")]
+    answers_file: Option<PathBuf>,
 
-#[derive(Debug, Deserialize)]
-struct Template {
-    name: String,
-    repo: String,
-    brief: String,
-}
+    /// Never prompt, fail if any answer is missing
+    #[arg(short, long, visible_alias = "non-interactive")]
+    yes: bool,
 
-#[derive(Deserialize, Debug)]
-#[allow(dead_code)]
-struct Project {
-    id: i32,
-    name: String,
-    ssh_url: String,
-    http_url: String,
-    web_url: String,
-    build_cmd: String,
-    dist: String,
-    description: String,
+    /// Only use the cached templates, never fetch them
+    #[arg(long)]
+    offline: bool,
+
+    /// Continue the unfinished creation from its last completed step
+    #[arg(long, conflicts_with = "abort")]
+    resume: bool,
+
+    /// Roll back the unfinished creation
+    #[arg(long)]
+    abort: bool,
 }
 
-// check the current directory and ask the user if they want to continue
-pub(crate) fn create(cli: &mut Cli) -> Result<()> {
-    // ask the user for the project name
-    let project_name = Text::new("Please enter the project name:")
-        .with_validator(|input: &str| {
-            let pattern = Regex::new(r"^[A-Za-z0-9]+(-[A-Za-z0-9]+)*$")
-                .with_context(|| "Failed to create a regex pattern to check the project name").unwrap();
-            if pattern.is_match(input) {
-                Ok(Validation::Valid)
-            } else {
-                Ok(Validation::Invalid("Your project name is invalid it must match the regexp `^[A-Za-z0-9]+(-[A-Za-z0-9]+)*$`".into()))
-            }
-        })
-        .prompt().with_context(|| "Failed to interact with the user")?;
+// ask the user for the project information and start the creation,
+// or resume the unfinished one recorded in the journal
+pub(crate) fn create(cli: &Cli, args: CreateArgs) -> Result<()> {
+    let pending = Journal::load()?;
+
+    if args.abort {
+        let mut journal =
+            pending.with_context(|| "There is no unfinished project creation to abort")?;
+        rollback(cli, &mut journal)?;
+        let output = serde_json::json!({
+            "project_name": journal.project_name,
+            "rolled_back": true,
+        });
+        return emit(cli.output, &output, || Ok(()));
+    }
 
-    let project_description = Text::new("Please enter the project description:")
-        .prompt()
-        .with_context(|| "Failed to interact with the user")?;
+    let mut journal = if args.resume {
+        let journal =
+            pending.with_context(|| "There is no unfinished project creation to resume")?;
+        tracing::info!(
+            "Resuming the creation of the project {}",
+            journal.project_name
+        );
+        journal
+    } else {
+        if let Some(journal) = pending {
+            return Err(anyhow::Error::msg(format!(
+                "The creation of the project {} is unfinished, run `yoo create --resume` to continue it or `yoo create --abort` to roll it back",
+                journal.project_name
+            )));
+        }
 
-    let build_cmd = Text::new("Please enter the build command of the project:")
-        .with_default("yarn build")
-        .prompt()
-        .with_context(|| "Failed to interact with the user")?;
+        let answers = match &args.answers_file {
+            Some(path) => args.answers.or(Answers::from_file(path)?),
+            None => args.answers,
+        };
 
-    let dist = Text::new("Please enter the dist of the project:")
-        .with_default("build")
-        .prompt()
-        .with_context(|| "Failed to interact with the user")?;
+        prepare(cli, answers, !args.yes, args.offline)?
+    };
+
+    match run_steps(cli, &mut journal, !args.yes, args.offline) {
+        Ok(_) => {
+            Journal::discard()?;
+            let output = serde_json::json!({
+                "project": journal.project,
+                "dir": journal.project_dir,
+                "template": {
+                    "name": journal.template_name,
+                    "repo": journal.template_repo,
+                    "commit": journal.template_commit,
+                },
+                "branches": ["master", "dev"],
+            });
+            emit(cli.output, &output, || Ok(()))
+        }
+        Err(err) => {
+            // the error of the step matters more than the one of the rollback
+            if let Err(rollback_err) = rollback(cli, &mut journal) {
+                tracing::error!(
+                    "{:#}, run `yoo create --abort` to finish the rollback",
+                    rollback_err
+                );
+            }
+            Err(err)
+        }
+    }
+}
+
+// check the current directory and ask the user if they want to continue
+fn prepare(cli: &Cli, answers: Answers, interactive: bool, offline: bool) -> Result<Journal> {
+    // ask the user for the project name
+    let project_name = ask_text(
+        answers.name,
+        "name",
+        "Please enter the project name:",
+        None,
+        interactive,
+        validate_project_name,
+    )?;
+
+    let project_description = ask_text(
+        answers.description,
+        "description",
+        "Please enter the project description:",
+        None,
+        interactive,
+        |_| Ok(()),
+    )?;
+
+    let build_cmd = ask_text(
+        answers.build_cmd,
+        "build-cmd",
+        "Please enter the build command of the project:",
+        Some("yarn build"),
+        interactive,
+        |_| Ok(()),
+    )?;
+
+    let dist = ask_text(
+        answers.dist,
+        "dist",
+        "Please enter the dist of the project:",
+        Some("build"),
+        interactive,
+        |_| Ok(()),
+    )?;
 
     // get working directory
     let working_dir = env::current_dir().with_context(|| "Failed to get the current directory")?;
 
     // project dir
     let project_dir = working_dir.join(project_name.as_str());
-    // project path
-    let project_path = project_dir
-        .to_str()
-        .with_context(|| "Failed to convert the path to string")?;
-
-    // if the project_dir is not exist, create it
-    if !project_dir.exists() {
-        std::fs::create_dir(&project_dir)
-            .with_context(|| "Failed to create the project directory")?;
-        tracing::info!("Successfully created the project directory")
-    }
 
     // if the project_dir is not empty, ask the user if they want to continue
-    let dir = project_dir
-        .read_dir()
-        .with_context(|| "Failed to read the current directory")?;
-
-    let arr = dir.collect::<Vec<Result<DirEntry, Error>>>();
-    // check if the current directory is empty
-    if !arr.is_empty() {
-        let ans = Confirm::new(
+    if project_dir.exists() && !is_empty_dir(&project_dir)? {
+        let ans = ask_confirm(
+            answers.clean,
+            "clean",
             format!(
                 "{}  The directory {} is not empty. Do you want to cleanup and continue?",
                 Emoji("⚠️", style("!!!").red().to_string().as_ref()),
                 project_name
             )
             .as_str(),
-        )
-        .with_default(false)
-        .prompt()
-        .with_context(|| "Failed to interact with the user")?;
+            interactive,
+        )?;
 
-        if ans {
-            for ele in arr {
-                let path = ele.with_context(|| "Failed to unwrap the element")?.path();
-                if path.is_dir() {
-                    remove_dir_all(path).with_context(|| "Failed to remove the directory")?;
-                } else {
-                    remove_file(path).with_context(|| "Failed to remove the file")?;
-                }
-            }
-            tracing::info!("Successfully cleaned up the directory {}", project_name);
-        } else {
+        // the directory is cleaned up once the template and the answers are resolved
+        if !ans {
             return Err(anyhow::Error::msg("User canceled the operation"));
         }
     }
 
-    let server_url = cli.server.as_deref().with_context(|| "SERVER is not set")?;
+    let spec = TemplateSpec::parse(answers.template.as_deref().unwrap_or_default());
+
+    let (template_name, template_repo) = match spec.kind() {
+        TemplateKind::Git => (spec.location.clone(), spec.location.clone()),
+        // keep the absolute path so the creation can be resumed from anywhere
+        TemplateKind::Path => {
+            let path = std::fs::canonicalize(spec.local_path())
+                .with_context(|| format!("Failed to find the template {}", spec.location))?;
+            let path = path
+                .to_str()
+                .with_context(|| "Failed to convert the path to string")?;
+            (spec.location.clone(), path.to_string())
+        }
+        TemplateKind::Name => {
+            let template = select_template(cli, &spec.location, interactive, offline)?;
+            (template.name, template.repo)
+        }
+    };
+
+    let journal = Journal {
+        project_name,
+        project_dir,
+        description: project_description,
+        build_cmd,
+        dist,
+        template_name,
+        template_repo,
+        template_ref: spec.reference,
+        template_subdir: spec.subdir,
+        template_commit: None,
+        vars: answers.vars.into_iter().collect(),
+        context: None,
+        hooks: vec![],
+        project: None,
+        steps: vec![],
+    };
+    journal.save()?;
 
-    tracing::debug!("server_url: {}", server_url);
+    Ok(journal)
+}
 
-    let pb = loading("Fetching the templates")?;
-    // show the project list
-    let resp = REQUEST
-        .get(format!("{}/v1/templates", server_url))
-        .send()
-        .with_context(|| "Failed to get the templates")?;
-    // sleep 10 seconds
-    pb.finish_and_clear();
+// find the template on the server by its name, or let the user select one
+fn select_template(cli: &Cli, name: &str, interactive: bool, offline: bool) -> Result<Template> {
+    let templates = fetch_templates(cli, offline)?;
 
-    if resp.status() != 200 {
-        return Err(anyhow::Error::msg("Failed to get the templates"));
+    if templates.is_empty() {
+        return Err(anyhow::Error::msg("No templates found"));
     }
 
-    let resp = resp
-        .json::<Response<PageData<Template>>>()
-        .with_context(|| "Failed to parse the response")?;
-
-    if resp.code != 0 {
-        return Err(anyhow::Error::msg("Failed to get the templates"));
+    if !name.is_empty() {
+        return templates
+            .into_iter()
+            .find(|x| x.name == name)
+            .with_context(|| format!("Failed to find the template {}", name));
     }
 
-    if resp.data.content.is_empty() {
-        return Err(anyhow::Error::msg("No templates found"));
+    if !interactive {
+        return Err(anyhow::Error::msg(
+            "Missing value for `--template`, set it with the flag or in the answers file",
+        ));
     }
 
     // show the options of the templates
-    let repo_options: Vec<String> = resp
-        .data
-        .content
+    let repo_options: Vec<String> = templates
         .iter()
         .map(|x| format!("{} -- {}", x.name, x.brief))
         .collect();
@@ -167,229 +252,369 @@ pub(crate) fn create(cli: &mut Cli) -> Result<()> {
         .prompt()
         .with_context(|| "Failed to interact with the user")?;
 
-    let selection = resp
-        .data
-        .content
-        .iter()
+    templates
+        .into_iter()
         .find(|x| format!("{} -- {}", x.name, x.brief) == repo_ans)
-        .with_context(|| "Failed to find the template")?;
-
-    // clone the repo
-    let pb = loading("Cloning")?;
-    let mut git_repo = git::clone(&selection.repo, project_path)?;
-    pb.finish_and_clear();
-    tracing::info!(
-        "Successfully created the project based on the template: {} -- {}",
-        selection.name,
-        selection.brief
-    );
-
-    cli.project_name = Some(project_name.clone());
-
-    // create project
-    let pb = loading("Creating git repo")?;
-
-    let payload = NewProject {
-        name: project_name.trim(),
-        description: project_description.trim(),
-        build_cmd: build_cmd.trim(),
-        dist: dist.trim(),
-    };
+        .with_context(|| "Failed to find the template")
+}
 
-    let project = create_project(cli, &payload)?;
+// run the steps which are not finished yet, every finished step is recorded in the journal
+fn run_steps(cli: &Cli, journal: &mut Journal, interactive: bool, offline: bool) -> Result<()> {
+    // project path
+    let project_path = journal
+        .project_dir
+        .to_str()
+        .with_context(|| "Failed to convert the path to string")?
+        .to_string();
 
-    cli.project_id = Some(project.id);
+    // if the project_dir is not exist, create it
+    if !journal.project_dir.exists() {
+        std::fs::create_dir(&journal.project_dir)
+            .with_context(|| "Failed to create the project directory")?;
+        journal.record(Step::CreateDir)?;
+        tracing::info!("Successfully created the project directory")
+    }
 
-    pb.finish_and_clear();
+    if !journal.is_done(Step::CloneTemplate) {
+        // the template is checked out next to the project directory first
+        let staging = journal
+            .project_dir
+            .with_file_name(format!(".{}.yoo-template", journal.project_name));
+        if staging.exists() {
+            remove_dir_all(&staging).with_context(|| "Failed to remove the staged template")?;
+        }
+        let staging_path = staging
+            .to_str()
+            .with_context(|| "Failed to convert the path to string")?;
+
+        let pb = loading("Cloning")?;
+        let result = checkout_template(
+            &journal.template_repo,
+            journal.template_ref.as_deref(),
+            journal.template_subdir.as_deref(),
+            staging_path,
+            offline,
+            &remote_options(cli).with_progress(pb.transfer()),
+        );
+        pb.finish_and_clear();
+
+        let result = result.and_then(|commit| {
+            journal.template_commit = commit;
+            install_template(journal, &staging, interactive)
+        });
+        if staging.exists() {
+            remove_dir_all(&staging).with_context(|| "Failed to remove the staged template")?;
+        }
+        result?;
 
-    tracing::info!("Successfully registered the project to the server");
+        journal.record(Step::CloneTemplate)?;
+        tracing::info!(
+            "Successfully created the project based on the template: {}",
+            journal.template_name
+        );
+    }
 
-    // remove the remote origin
-    git_repo.delete_remote()?;
-    tracing::info!("Successfully removed the remote origin");
+    let mut git_repo = git::open_repo(&project_path)?;
+
+    if !journal.is_done(Step::RenderTemplate) {
+        if let Some(manifest) = Manifest::load(&journal.project_dir)? {
+            let context = match journal.context.take() {
+                Some(context) => context,
+                None => {
+                    let mut context = RenderContext::new(
+                        &journal.project_name,
+                        &journal.description,
+                        &journal.build_cmd,
+                        &journal.dist,
+                    );
+                    manifest.ask(&mut context, &journal.vars, interactive)?;
+                    context
+                }
+            };
 
-    // add the remote origin
-    git_repo.set_remote(project.ssh_url.as_str())?;
-    tracing::info!("Successfully added the remote origin: {}", project.ssh_url);
+            // keep the answers in case the rendering is interrupted
+            journal.hooks = manifest.hooks(&context)?;
+            journal.context = Some(context.clone());
+            journal.save()?;
 
-    // change working dir
-    git_repo.change_working_dir(Some(project_path.to_string()))?;
+            let pb = loading("Rendering the template")?;
+            manifest.apply(&journal.project_dir, &context)?;
+            pb.finish_and_clear();
+            tracing::info!("Successfully rendered the template");
+        }
+        journal.record(Step::RenderTemplate)?;
+    }
 
-    // push the master branch to the remote origin
-    let pb = loading("Pushing")?;
-    git_repo.push("master")?;
-    pb.finish_and_clear();
-    tracing::info!("Successfully pushed the master branch to the remote origin");
+    for (index, hook) in journal.hooks.clone().iter().enumerate() {
+        if !journal.is_done(Step::RunHook(index)) {
+            let pb = loading(&hook.name)?;
+            hook.run(&journal.project_dir)?;
+            pb.finish_and_clear();
+            journal.record(Step::RunHook(index))?;
+            tracing::info!("Successfully ran the hook: {}", hook.name);
+        }
+    }
 
-    // create and checkout to dev branch
-    git_repo.checkout_to_branch("dev")?;
-    tracing::info!("Successfully created and checked out to dev branch");
+    if !journal.is_done(Step::RegisterProject) {
+        // create project
+        let pb = loading("Creating git repo")?;
 
-    // push the dev branch to the remote origin
-    let pb = loading("Pushing")?;
-    git_repo.push("dev")?;
-    pb.finish_and_clear();
-    tracing::info!("Successfully pushed the dev branch to the remote origin");
+        let payload = NewProject {
+            name: &journal.project_name,
+            description: &journal.description,
+            build_cmd: &journal.build_cmd,
+            dist: &journal.dist,
+        };
 
-    // reset the working dir
-    git_repo.change_working_dir(None)?;
+        let project = YooApi::new(cli).create_project(&payload)?;
+        pb.finish_and_clear();
 
-    tracing::info!("Now everything is ready. You can start to work on your project. keep coding!");
+        journal.project = Some(project);
+        journal.record(Step::RegisterProject)?;
+        tracing::info!("Successfully registered the project to the server");
+    }
 
-    Ok(())
-    // use gitlab api to create a new repo and cache the info
-}
+    if !journal.is_done(Step::WriteMetadata) {
+        // remember the registered project and where it comes from so that it can be upgraded later
+        let context = journal.context.clone().unwrap_or_else(|| {
+            RenderContext::new(
+                &journal.project_name,
+                &journal.description,
+                &journal.build_cmd,
+                &journal.dist,
+            )
+        });
+        let project = journal
+            .project
+            .as_ref()
+            .with_context(|| "The project is not registered")?;
+        let metadata = Metadata {
+            project: Some(ProjectInfo::new(project, cli.server.as_ref().unwrap())),
+            template: Some(TemplateProvenance {
+                name: journal.template_name.clone(),
+                repo: journal.template_repo.clone(),
+                reference: journal.template_ref.clone(),
+                subdir: journal.template_subdir.clone(),
+                commit: journal.template_commit.clone(),
+                context,
+            }),
+            config: None,
+        };
+        metadata.save(&journal.project_dir)?;
+        journal.record(Step::WriteMetadata)?;
+    }
 
-#[derive(Debug, Serialize)]
-struct NewProject<'a> {
-    name: &'a str,
-    build_cmd: &'a str,
-    dist: &'a str,
-    description: &'a str,
-}
+    if !journal.is_done(Step::CommitProject) {
+        git_repo.commit_all(&format!(
+            "chore: create {} from {}",
+            journal.project_name, journal.template_name
+        ))?;
+        journal.record(Step::CommitProject)?;
+    }
 
-fn create_project(cli: &Cli, payload: &NewProject) -> Result<Project> {
-    let mut authorization = match read_authorization() {
-        Ok(auth) => auth,
-        Err(_) => {
-            let auth = login(
-                cli.server.as_ref().unwrap(),
-                cli.server_email.as_ref().unwrap(),
-                cli.server_password.as_ref().unwrap(),
-            )?;
-            write_authorization(&auth)?;
-            auth
+    let ssh_url = journal
+        .project
+        .as_ref()
+        .map(|project| project.ssh_url.clone())
+        .with_context(|| "The project is not registered")?;
+
+    if !journal.is_done(Step::RewriteRemote) {
+        // remove the remote origin
+        if git_repo.has_remote("origin") {
+            git_repo.delete_remote()?;
+            tracing::info!("Successfully removed the remote origin");
         }
-    };
 
-    let resp = create_project_to_server(cli, &payload, &authorization)?;
+        // add the remote origin
+        git_repo.set_remote(ssh_url.as_str())?;
+        journal.record(Step::RewriteRemote)?;
+        tracing::info!("Successfully added the remote origin: {}", ssh_url);
+    }
 
-    match resp.status() {
-        StatusCode::OK => {
-            let res = resp
-                .json::<Response<Project>>()
-                .with_context(|| "Failed to parse the response")?;
+    // change working dir
+    git_repo.change_working_dir(Some(project_path))?;
+
+    if !journal.is_done(Step::PushMaster) {
+        // push the master branch to the remote origin
+        let pb = loading("Pushing")?;
+        git_repo.push("master", &remote_options(cli).with_progress(pb.transfer()))?;
+        pb.finish_and_clear();
+        journal.record(Step::PushMaster)?;
+        tracing::info!("Successfully pushed the master branch to the remote origin");
+    }
 
-            Ok(res.data)
-        }
-        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => {
-            authorization = login(
-                cli.server.as_ref().unwrap(),
-                cli.server_email.as_ref().unwrap(),
-                cli.server_password.as_ref().unwrap(),
-            )?;
+    if !journal.is_done(Step::CheckoutDev) {
+        // create and checkout to dev branch
+        git_repo.checkout_to_branch("dev")?;
+        journal.record(Step::CheckoutDev)?;
+        tracing::info!("Successfully created and checked out to dev branch");
+    }
 
-            write_authorization(&authorization)?;
+    if !journal.is_done(Step::PushDev) {
+        // push the dev branch to the remote origin
+        let pb = loading("Pushing")?;
+        git_repo.push("dev", &remote_options(cli).with_progress(pb.transfer()))?;
+        pb.finish_and_clear();
+        journal.record(Step::PushDev)?;
+        tracing::info!("Successfully pushed the dev branch to the remote origin");
+    }
 
-            let resp = create_project_to_server(cli, &payload, &authorization)?;
+    // reset the working dir
+    git_repo.change_working_dir(None)?;
 
-            if resp.status() != StatusCode::OK {
-                return Err(anyhow::Error::msg(
-                    "Failed to register the project".to_string(),
-                ));
-            }
+    tracing::info!("Now everything is ready. You can start to work on your project. keep coding!");
 
-            let res = resp
-                .json::<Response<Project>>()
-                .with_context(|| "Failed to parse the response")?;
+    Ok(())
+    // use gitlab api to create a new repo and cache the info
+}
 
-            Ok(res.data)
+// resolve the answers of the staged template, then replace the content of the project
+// directory with it, a missing answer leaves the directory as it is
+fn install_template(journal: &mut Journal, staging: &Path, interactive: bool) -> Result<()> {
+    if journal.context.is_none() {
+        if let Some(manifest) = Manifest::load(staging)? {
+            let mut context = RenderContext::new(
+                &journal.project_name,
+                &journal.description,
+                &journal.build_cmd,
+                &journal.dist,
+            );
+            manifest.ask(&mut context, &journal.vars, interactive)?;
+            journal.hooks = manifest.hooks(&context)?;
+            journal.context = Some(context);
         }
-        _ => Err(anyhow::Error::msg(
-            "Failed to register the project".to_string(),
-        )),
     }
-}
 
-fn create_project_to_server(
-    cli: &Cli,
-    payload: &&NewProject,
-    authorization: &String,
-) -> Result<reqwest::blocking::Response> {
-    REQUEST
-        .post(format!("{}/v1/projects", cli.server.as_ref().unwrap()))
-        .header("Authorization", authorization)
-        .json(&payload)
-        .send()
-        .with_context(|| "Failed to register the project")
-}
+    clear_dir(&journal.project_dir)?;
+    let entries = staging
+        .read_dir()
+        .with_context(|| "Failed to read the staged template")?;
+    for entry in entries {
+        let entry = entry.with_context(|| "Failed to read the directory entry")?;
+        rename(entry.path(), journal.project_dir.join(entry.file_name()))
+            .with_context(|| "Failed to move the template into the project directory")?;
+    }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct AuthBody {
-    access_token: String,
-    refresh_token: String,
-    token_type: String,
+    Ok(())
 }
 
-pub fn login(server_url: &str, email: &str, password: &str) -> Result<String> {
-    let mut map = HashMap::new();
-    map.insert("email", email);
-    map.insert("password", password);
-
-    let resp = REQUEST
-        .post(format!("{}/v1/users/login", server_url))
-        .json(&map)
-        .send()?;
+// undo the finished steps in reverse order, the journal is kept up to date
+// so that an interrupted rollback can be continued with `yoo create --abort`
+fn rollback(cli: &Cli, journal: &mut Journal) -> Result<()> {
+    let pb = loading("Cleaning up...")?;
 
-    // check the status code
-    if resp.status() != 200 {
-        return Err(anyhow::Error::msg("Failed to login".to_string()));
+    while let Some(step) = journal.steps.last().copied() {
+        if let Err(err) = undo(cli, journal, step) {
+            pb.finish_and_clear();
+            return Err(err.context(format!("Failed to roll back the step {:?}", step)));
+        }
+        journal.steps.pop();
+        journal.save()?;
     }
 
-    let auth_resp = resp
-        .json::<Response<AuthBody>>()
-        .with_context(|| "Failed to parse the response")?;
-
-    if auth_resp.code != 0 {
-        return Err(anyhow::Error::msg("Failed to login".to_string()));
-    }
+    pb.finish_and_clear();
+    Journal::discard()?;
+    tracing::info!("Successfully cleaned up");
 
-    Ok(format!(
-        "{} {}",
-        auth_resp.data.token_type, auth_resp.data.access_token
-    ))
+    Ok(())
 }
 
-pub fn read_authorization() -> Result<String> {
-    // get home dir
-    let home_dir = dirs::home_dir().with_context(|| "Failed to get the home dir")?;
-    let cache_file = home_dir.join(format!("{}/{}", CACHE_DIR, CACHE_FILE));
-    // read cache json file
-    let file = File::open(cache_file)?;
-    let reader = BufReader::new(file);
-    let cache: HashMap<String, String> = serde_json::from_reader(reader)?;
-    let token = cache.get("authorization");
-
-    if let Some(token) = token {
-        Ok(token.to_string())
-    } else {
-        Err(anyhow::Error::msg("Failed to read the token"))
+fn undo(cli: &Cli, journal: &Journal, step: Step) -> Result<()> {
+    tracing::debug!("Rolling back the step {:?}", step);
+    match step {
+        Step::CreateDir => remove_dir_all(&journal.project_dir)
+            .with_context(|| "Failed to remove the project directory"),
+        Step::CloneTemplate => clear_dir(&journal.project_dir),
+        Step::RunHook(index) => match journal.hooks.get(index) {
+            Some(hook) => hook.undo(&journal.project_dir),
+            None => Ok(()),
+        },
+        // the rendered files and the commit go away with the cloned ones
+        Step::RenderTemplate | Step::WriteMetadata | Step::CommitProject => Ok(()),
+        Step::RegisterProject => match &journal.project {
+            Some(project) => Ok(YooApi::new(cli).delete_project(project.id)?),
+            None => Ok(()),
+        },
+        // the remote and the branches go away with the server project and the local directory
+        Step::RewriteRemote | Step::PushMaster | Step::CheckoutDev | Step::PushDev => Ok(()),
     }
 }
 
-pub fn write_authorization(authorization: &str) -> Result<()> {
-    let home_dir = dirs::home_dir().with_context(|| "Failed to get the home dir")?;
-    let cache_file = home_dir.join(format!("{}/{}", CACHE_DIR, CACHE_FILE));
-
-    // read cache json file
-    let mut cache: HashMap<String, String> = HashMap::new();
-    let file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(cache_file)?;
-
-    if file.metadata()?.len() > 0 {
-        let reader = BufReader::new(&file);
-        cache = serde_json::from_reader(reader)?;
-    }
+fn is_empty_dir(dir: &Path) -> Result<bool> {
+    let mut entries = dir
+        .read_dir()
+        .with_context(|| "Failed to read the current directory")?;
+    Ok(entries.next().is_none())
+}
+
+// remove everything inside the directory but keep the directory itself
+fn clear_dir(dir: &Path) -> Result<()> {
+    let entries = dir
+        .read_dir()
+        .with_context(|| "Failed to read the current directory")?;
 
-    cache.insert("authorization".to_string(), authorization.to_string());
+    for ele in entries {
+        let path = ele.with_context(|| "Failed to unwrap the element")?.path();
+        if path.is_dir() {
+            remove_dir_all(path).with_context(|| "Failed to remove the directory")?;
+        } else {
+            remove_file(path).with_context(|| "Failed to remove the file")?;
+        }
+    }
 
-    // write cache json file
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &cache)?;
     Ok(())
 }
+
+// test
+#[cfg(test)]
+mod test {
+    use super::install_template;
+    use crate::journal::Journal;
+    use std::{collections::BTreeMap, fs};
+
+    #[test]
+    fn test_install_template() {
+        let dir = std::env::temp_dir().join(format!("yoo-install-{}", std::process::id()));
+        let (staging, project_dir) = (dir.join("staging"), dir.join("demo"));
+        fs::create_dir_all(&staging).unwrap();
+        fs::create_dir_all(&project_dir).unwrap();
+        fs::write(project_dir.join("keep"), "keep").unwrap();
+        fs::write(staging.join("package.json"), "{}").unwrap();
+        fs::write(
+            staging.join("yoo-template.toml"),
+            "[[variables]]\nname = \"title\"\n",
+        )
+        .unwrap();
+
+        let mut journal = Journal {
+            project_name: "demo".to_string(),
+            project_dir: project_dir.clone(),
+            description: String::new(),
+            build_cmd: String::new(),
+            dist: String::new(),
+            template_name: "local".to_string(),
+            template_repo: staging.to_string_lossy().to_string(),
+            template_ref: None,
+            template_subdir: None,
+            template_commit: None,
+            vars: BTreeMap::new(),
+            context: None,
+            hooks: vec![],
+            project: None,
+            steps: vec![],
+        };
+
+        // the missing answer fails before the directory is cleaned
+        assert!(install_template(&mut journal, &staging, false).is_err());
+        assert!(project_dir.join("keep").exists());
+        assert!(!project_dir.join("package.json").exists());
+
+        journal.vars.insert("title".to_string(), "Demo".to_string());
+        install_template(&mut journal, &staging, false).unwrap();
+        assert!(!project_dir.join("keep").exists());
+        assert!(project_dir.join("package.json").exists());
+        assert_eq!(journal.context.unwrap().vars["title"], "Demo");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,288 @@
+use anyhow::{Context, Result};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use sha2::Sha256;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::CACHE_DIR;
+
+pub(crate) const CREDENTIALS_FILE: &str = "credentials";
+pub(crate) const CREDENTIALS_KEY_FILE: &str = "credentials.key";
+
+// the header of the credentials file: magic, version and how the key is derived
+const MAGIC: &[u8; 4] = b"YOO\x01";
+const KDF_KEY_FILE: u8 = 0;
+const KDF_PASSPHRASE: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+/// A place to keep the secrets, e.g. the tokens and the password
+///
+/// The encrypted file is the only backend for now, an OS keyring can be added by implementing it
+pub(crate) trait CredentialStore {
+    /// The name of the backend, shown to the user
+    fn name(&self) -> String;
+    fn get(&self, key: &str) -> Result<Option<String>>;
+    fn set(&self, key: &str, value: &str) -> Result<()>;
+    fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// the credential store of the current user
+pub(crate) fn store() -> Result<Box<dyn CredentialStore>> {
+    let home_dir = dirs::home_dir().with_context(|| "Failed to get the home dir")?;
+    let dir = home_dir.join(CACHE_DIR);
+
+    Ok(Box::new(EncryptedFileStore {
+        path: dir.join(CREDENTIALS_FILE),
+        key_path: dir.join(CREDENTIALS_KEY_FILE),
+        passphrase: env::var("YOO_CREDENTIALS_PASSPHRASE").ok(),
+    }))
+}
+
+/// The secrets encrypted with ChaCha20-Poly1305 in a file only readable by the owner
+///
+/// The key is derived from YOO_CREDENTIALS_PASSPHRASE when it is set,
+/// otherwise it is a random secret kept in a file next to the credentials
+pub(crate) struct EncryptedFileStore {
+    pub path: PathBuf,
+    pub key_path: PathBuf,
+    pub passphrase: Option<String>,
+}
+
+impl CredentialStore for EncryptedFileStore {
+    fn name(&self) -> String {
+        format!("encrypted file {}", self.path.display())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.read()?.remove(key))
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        let mut secrets = self.read()?;
+        secrets.insert(key.to_string(), value.to_string());
+        self.write(&secrets)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let mut secrets = self.read()?;
+        if secrets.remove(key).is_some() {
+            self.write(&secrets)?;
+        }
+        Ok(())
+    }
+}
+
+impl EncryptedFileStore {
+    fn read(&self) -> Result<BTreeMap<String, String>> {
+        if !self.path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        let data = fs::read(&self.path).with_context(|| "Failed to read the credentials")?;
+        let header_len = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+        if data.len() < header_len || &data[..MAGIC.len()] != MAGIC {
+            return Err(anyhow::Error::msg(format!(
+                "The credentials file {} is corrupted, remove it and log in again",
+                self.path.display()
+            )));
+        }
+
+        let kdf = data[MAGIC.len()];
+        let salt = &data[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_LEN];
+        let nonce = Nonce::from_slice(&data[header_len - NONCE_LEN..header_len]);
+
+        let key = match kdf {
+            KDF_PASSPHRASE => {
+                let passphrase = self.passphrase.as_deref().with_context(|| {
+                    "The credentials are encrypted with a passphrase, set YOO_CREDENTIALS_PASSPHRASE"
+                })?;
+                derive_key(passphrase, salt)
+            }
+            KDF_KEY_FILE => self.read_key()?.with_context(|| {
+                format!(
+                    "The key file {} is missing, remove the credentials and log in again",
+                    self.key_path.display()
+                )
+            })?,
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "The credentials file {} is corrupted or written by a newer yoo, remove it and log in again",
+                    self.path.display()
+                )))
+            }
+        };
+
+        let plain = ChaCha20Poly1305::new(&key)
+            .decrypt(nonce, &data[header_len..])
+            .map_err(|_| {
+                anyhow::Error::msg(
+                    "Failed to decrypt the credentials, the key or the passphrase is wrong",
+                )
+            })?;
+
+        serde_json::from_slice(&plain).with_context(|| "Failed to parse the credentials")
+    }
+
+    fn write(&self, secrets: &BTreeMap<String, String>) -> Result<()> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+
+        let (kdf, key) = match &self.passphrase {
+            Some(passphrase) => (KDF_PASSPHRASE, derive_key(passphrase, &salt)),
+            None => match self.read_key()? {
+                Some(key) => (KDF_KEY_FILE, key),
+                None => {
+                    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                    write_private(&self.key_path, &key)
+                        .with_context(|| "Failed to write the key file")?;
+                    (KDF_KEY_FILE, key)
+                }
+            },
+        };
+
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plain = serde_json::to_vec(secrets)?;
+        let cipher = ChaCha20Poly1305::new(&key)
+            .encrypt(&nonce, plain.as_slice())
+            .map_err(|_| anyhow::Error::msg("Failed to encrypt the credentials"))?;
+
+        let mut data = Vec::with_capacity(MAGIC.len() + 1 + SALT_LEN + NONCE_LEN + cipher.len());
+        data.extend_from_slice(MAGIC);
+        data.push(kdf);
+        data.extend_from_slice(&salt);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&cipher);
+
+        write_private(&self.path, &data).with_context(|| "Failed to write the credentials")
+    }
+
+    fn read_key(&self) -> Result<Option<Key>> {
+        if !self.key_path.exists() {
+            return Ok(None);
+        }
+
+        let key = fs::read(&self.key_path).with_context(|| "Failed to read the key file")?;
+        if key.len() != 32 {
+            return Err(anyhow::Error::msg(format!(
+                "The key file {} is corrupted",
+                self.key_path.display()
+            )));
+        }
+        Ok(Some(*Key::from_slice(&key)))
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Key {
+    let mut key = Key::default();
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+// write the file only readable by the owner, it is written to a temp file and renamed
+// so that a failed write never leaves a truncated file behind
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    let name = path
+        .file_name()
+        .with_context(|| format!("Invalid path {}", path.display()))?;
+    let temp_file = path.with_file_name(format!("{}.tmp", name.to_string_lossy()));
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).truncate(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let result = options.open(&temp_file).and_then(|mut file| {
+        // a leftover temp file keeps its permissions when it is opened
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&temp_file, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_file);
+    }
+    Ok(result?)
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{CredentialStore, EncryptedFileStore};
+    use std::{env, fs, process};
+
+    fn temp_store(name: &str, passphrase: Option<&str>) -> EncryptedFileStore {
+        let dir = env::temp_dir().join(format!("yoo-credentials-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        EncryptedFileStore {
+            path: dir.join("credentials"),
+            key_path: dir.join("credentials.key"),
+            passphrase: passphrase.map(|x| x.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_key_file_store() {
+        let store = temp_store("key", None);
+        store.set("session", "Bearer token").unwrap();
+
+        assert_eq!(
+            store.get("session").unwrap().as_deref(),
+            Some("Bearer token")
+        );
+        assert!(!fs::read(&store.path)
+            .unwrap()
+            .windows(5)
+            .any(|x| x == b"token"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&store.path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        assert!(!store.path.with_file_name("credentials.tmp").exists());
+
+        store.delete("session").unwrap();
+        assert_eq!(store.get("session").unwrap(), None);
+
+        // an unknown way to derive the key is refused
+        let mut data = fs::read(&store.path).unwrap();
+        data[4] = 9;
+        fs::write(&store.path, data).unwrap();
+        let err = store.get("session").unwrap_err().to_string();
+        assert!(err.contains("newer"));
+    }
+
+    #[test]
+    fn test_passphrase_store() {
+        let store = temp_store("passphrase", Some("secret"));
+        store.set("password", "pw").unwrap();
+        assert_eq!(store.get("password").unwrap().as_deref(), Some("pw"));
+
+        let wrong = EncryptedFileStore {
+            passphrase: Some("wrong".to_string()),
+            ..temp_store("passphrase-wrong", None)
+        };
+        fs::copy(&store.path, &wrong.path).unwrap();
+        assert!(wrong.get("password").is_err());
+    }
+}
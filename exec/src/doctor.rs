@@ -0,0 +1,509 @@
+use anyhow::Result;
+use clap::Args;
+use console::style;
+use serde::Serialize;
+use std::{fs, path::PathBuf, process::Command, time::Duration};
+
+use crate::{
+    api::{ApiError, YooApi},
+    auth::read_session,
+    check_config,
+    credentials::CREDENTIALS_FILE,
+    loading::loading,
+    metadata::{Metadata, METADATA_FILE},
+    output::emit,
+    remote::remote_options,
+    Cli, CACHE_DIR, REQUEST,
+};
+
+#[derive(Args)]
+pub(crate) struct DoctorArgs {
+    /// Print the checks as json
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// The result of a check, the hint tells how to fix it
+#[derive(Debug, Serialize)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    checks: &'a [Check],
+    passed: usize,
+    warnings: usize,
+    failed: usize,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: impl Into<String>) -> Check {
+        Check {
+            name,
+            status: Status::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Check {
+        Check {
+            name,
+            status: Status::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Check {
+        Check {
+            name,
+            status: Status::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+// check the environment yoo depends on, the command fails if any check fails
+pub(crate) fn doctor(mut cli: Cli, args: DoctorArgs) -> Result<()> {
+    let pb = loading("Checking the environment")?;
+
+    let mut checks = check_config_consistency(&mut cli);
+    checks.push(check_git(&cli));
+    checks.push(check_ssh_keys());
+    checks.push(check_gitlab_ssh(&cli));
+    if cli.server.is_some() {
+        checks.push(check_server(&cli));
+        checks.push(check_credentials(&cli));
+    }
+    checks.push(check_cache_dir());
+    checks.extend(check_toolchain());
+
+    pb.finish_and_clear();
+
+    let count = |status| checks.iter().filter(|x| x.status == status).count();
+    let (passed, warnings, failed) = (
+        count(Status::Pass),
+        count(Status::Warn),
+        count(Status::Fail),
+    );
+
+    let report = Report {
+        checks: &checks,
+        passed,
+        warnings,
+        failed,
+    };
+    emit(cli.output.or_json(args.json), &report, || {
+        let width = checks
+            .iter()
+            .map(|x| x.name.len())
+            .max()
+            .unwrap_or_default();
+        for check in &checks {
+            let status = match check.status {
+                Status::Pass => style("PASS").green(),
+                Status::Warn => style("WARN").yellow(),
+                Status::Fail => style("FAIL").red(),
+            };
+            println!(
+                "{}  {:width$}  {}",
+                status,
+                check.name,
+                check.message,
+                width = width
+            );
+            if let Some(hint) = &check.hint {
+                println!("      {:width$}  {}", "", style(hint).dim(), width = width);
+            }
+        }
+        println!(
+            "\n{} passed, {} warnings, {} failed",
+            passed, warnings, failed
+        );
+        Ok(())
+    })?;
+
+    if failed > 0 {
+        return Err(anyhow::Error::msg(format!("{} checks failed", failed)));
+    }
+    Ok(())
+}
+
+fn check_config_consistency(cli: &mut Cli) -> Vec<Check> {
+    if let Err(err) = check_config(cli) {
+        return vec![Check::fail(
+            "config",
+            format!("{:#}", err),
+            "run `yoo config explain` to see where the settings come from",
+        )];
+    }
+
+    let server = cli.server.clone().unwrap_or_default();
+    let mut checks = vec![Check::pass("config", format!("The server is {}", server))];
+
+    if cli.server_email.is_none() {
+        checks.push(Check::warn(
+            "email",
+            "The email is not set, it is prompted when logging in",
+            "run `yoo config set email <EMAIL>` or set YOO_SERVER_EMAIL",
+        ));
+    }
+
+    match Metadata::discover() {
+        Ok(Some((root, metadata))) => match metadata.project {
+            Some(project) if !project.server.is_empty() && project.server != server => {
+                checks.push(Check::warn(
+                    "project",
+                    format!(
+                        "The project {} is registered on {}, but the server is {}",
+                        project.name, project.server, server
+                    ),
+                    "switch the profile with `--profile` or run `yoo config set --project server`",
+                ))
+            }
+            Some(project) => checks.push(Check::pass(
+                "project",
+                format!("The project {} ({})", project.name, project.id),
+            )),
+            None => checks.push(Check::warn(
+                "project",
+                format!("The {} has no registered project", root.display()),
+                "run `yoo init` to register it",
+            )),
+        },
+        Ok(None) => {}
+        Err(err) => checks.push(Check::fail(
+            "project",
+            format!("{:#}", err),
+            format!("fix or remove the {}", METADATA_FILE),
+        )),
+    }
+
+    checks
+}
+
+fn check_git(cli: &Cli) -> Check {
+    // the remotes are reached with libgit2, the git binary is only run by `git.fallback`
+    let fallback = remote_options(cli).fallback;
+    match git::version() {
+        Ok(version) if version_at_least(&version, &[2, 20]) => {
+            Check::pass("git", format!("git {}", version))
+        }
+        Ok(version) => Check::warn(
+            "git",
+            format!("git {} is older than 2.20", version),
+            "upgrade git, see https://git-scm.com/downloads",
+        ),
+        Err(err) if fallback => Check::fail(
+            "git",
+            format!("{:#}", err),
+            "install git and add it to the PATH, or turn off `git.fallback`",
+        ),
+        Err(_) => Check::warn(
+            "git",
+            "git is not found, it is only needed by `git.fallback`",
+            "install git to fall back to it when a clone or a push fails, see https://git-scm.com/downloads",
+        ),
+    }
+}
+
+fn check_ssh_keys() -> Check {
+    // the keys in the agent, ssh-add exits with 1 when the agent has no keys and 2 without an agent
+    if let Some(output) = run("ssh-add", &["-l"]) {
+        let count = output.lines().count();
+        return Check::pass(
+            "ssh keys",
+            format!("{} keys are loaded in the ssh-agent", count),
+        );
+    }
+
+    let key_files: Vec<PathBuf> = dirs::home_dir()
+        .map(|home_dir| {
+            ["id_ed25519", "id_ecdsa", "id_rsa"]
+                .iter()
+                .map(|name| home_dir.join(".ssh").join(name))
+                .filter(|path| path.exists())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    match key_files.first() {
+        Some(path) => Check::pass("ssh keys", format!("Found {}", path.display())),
+        None => Check::fail(
+            "ssh keys",
+            "There is no ssh key in the ssh-agent or ~/.ssh",
+            "run `ssh-keygen -t ed25519` and add the public key to GitLab",
+        ),
+    }
+}
+
+/// The name of the ssh check, it runs OpenSSH which reads ~/.ssh/config,
+/// unlike the libgit2 connection of clone and push
+const GITLAB_SSH: &str = "gitlab ssh (openssh)";
+
+fn check_gitlab_ssh(cli: &Cli) -> Check {
+    let host = match cli.config.get("gitlab.server").and_then(host_of) {
+        Some(host) => host,
+        None => {
+            return Check::warn(
+                GITLAB_SSH,
+                "gitlab.server is not set, the ssh auth is not checked",
+                "run `yoo config set gitlab.server <URL>` or set YOO_GITLAB_SERVER",
+            )
+        }
+    };
+
+    let output = Command::new("ssh")
+        .args([
+            "-T",
+            "-o",
+            "BatchMode=yes",
+            "-o",
+            "ConnectTimeout=5",
+            "-o",
+            // a diagnosis doesn't add the unknown hosts to the known hosts
+            "StrictHostKeyChecking=yes",
+        ])
+        .arg(format!("git@{}", host))
+        .output();
+
+    match output {
+        Ok(output) => {
+            let text = format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            if text.contains("Welcome to GitLab") {
+                Check::pass(
+                    GITLAB_SSH,
+                    format!("{} accepts the ssh key of OpenSSH", host),
+                )
+            } else if text.contains("REMOTE HOST IDENTIFICATION HAS CHANGED") {
+                Check::fail(
+                    GITLAB_SSH,
+                    format!("The host key of {} has changed", host),
+                    "make sure the new key is genuine, then update it in ~/.ssh/known_hosts",
+                )
+            } else if text.contains("Host key verification failed") {
+                Check::warn(
+                    GITLAB_SSH,
+                    format!("{} is not in the known hosts", host),
+                    format!(
+                        "run `ssh -T git@{}` once to check and add its host key",
+                        host
+                    ),
+                )
+            } else if text.contains("Permission denied") {
+                Check::fail(
+                    GITLAB_SSH,
+                    format!("{} rejects the ssh key", host),
+                    "add your public key in the GitLab preferences, SSH Keys",
+                )
+            } else {
+                Check::fail(
+                    GITLAB_SSH,
+                    format!("Failed to connect to {}: {}", host, text.trim()),
+                    "check the network, or set the Port of the host in ~/.ssh/config",
+                )
+            }
+        }
+        // yoo doesn't need OpenSSH, only the check does
+        Err(err) => Check::warn(
+            GITLAB_SSH,
+            format!("Failed to run ssh, the ssh auth is not checked: {}", err),
+            "install OpenSSH and add it to the PATH",
+        ),
+    }
+}
+
+fn check_server(cli: &Cli) -> Check {
+    let server = cli.server.as_deref().unwrap_or_default();
+
+    // any response means the server is reachable
+    match REQUEST.get(server).timeout(Duration::from_secs(5)).send() {
+        Ok(resp) => Check::pass(
+            "server",
+            format!("{} is reachable ({})", server, resp.status()),
+        ),
+        Err(err) => Check::fail(
+            "server",
+            format!("Failed to reach {}: {}", server, err),
+            "check the address with `yoo config explain server`, the network and the proxy",
+        ),
+    }
+}
+
+fn check_credentials(cli: &Cli) -> Check {
+    match read_session(cli) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Check::warn(
+                "credentials",
+                "You are not logged in",
+                "run `yoo auth login`",
+            )
+        }
+        Err(err) => {
+            return Check::fail(
+                "credentials",
+                format!("{:#}", err),
+                "set YOO_CREDENTIALS_PASSPHRASE, or remove ~/.yoo/credentials and log in again",
+            )
+        }
+    }
+
+    // the session is only refreshed, logging in again may prompt the password
+    match YooApi::without_relogin(cli).me() {
+        Ok(user) => Check::pass("credentials", format!("Logged in as {}", user.email)),
+        Err(ApiError::Session(err)) => Check::warn(
+            "credentials",
+            format!("{:#}", err),
+            "run `yoo auth login` to log in again",
+        ),
+        Err(err) => Check::fail(
+            "credentials",
+            format!("Failed to log in: {}", err),
+            "run `yoo auth login` to log in again",
+        ),
+    }
+}
+
+fn check_cache_dir() -> Check {
+    let cache_dir = match dirs::home_dir() {
+        Some(home_dir) => home_dir.join(CACHE_DIR),
+        None => {
+            return Check::fail(
+                "cache dir",
+                "Failed to get the home dir",
+                "set the HOME env",
+            )
+        }
+    };
+
+    let probe = cache_dir.join(".doctor");
+    if let Err(err) = fs::write(&probe, b"").and_then(|_| fs::remove_file(&probe)) {
+        return Check::fail(
+            "cache dir",
+            format!("{} is not writable: {}", cache_dir.display(), err),
+            format!(
+                "check the owner and the permissions of {}",
+                cache_dir.display()
+            ),
+        );
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let credentials = cache_dir.join(CREDENTIALS_FILE);
+        if let Ok(metadata) = fs::metadata(&credentials) {
+            if metadata.permissions().mode() & 0o077 != 0 {
+                return Check::warn(
+                    "cache dir",
+                    format!("{} is readable by other users", credentials.display()),
+                    format!("run `chmod 600 {}`", credentials.display()),
+                );
+            }
+        }
+    }
+
+    Check::pass("cache dir", format!("{} is writable", cache_dir.display()))
+}
+
+fn check_toolchain() -> Vec<Check> {
+    let node = match run("node", &["--version"]) {
+        Some(version) if version_at_least(version.trim_start_matches('v'), &[16]) => {
+            Check::pass("node", format!("node {}", version))
+        }
+        Some(version) => Check::warn(
+            "node",
+            format!("node {} is older than 16", version),
+            "upgrade node, see https://nodejs.org",
+        ),
+        None => Check::warn(
+            "node",
+            "node is not found, the build commands of the projects can't run",
+            "install node, see https://nodejs.org",
+        ),
+    };
+
+    let yarn = match run("yarn", &["--version"]) {
+        Some(version) => Check::pass("yarn", format!("yarn {}", version)),
+        None => Check::warn(
+            "yarn",
+            "yarn is not found, the projects are built with `yarn build` by default",
+            "run `corepack enable` or `npm install -g yarn`",
+        ),
+    };
+
+    vec![node, yarn]
+}
+
+// the output of a program which exits successfully
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string()).filter(|x| !x.is_empty())
+}
+
+// the host of an url like `https://gitlab.com/` or `gitlab.com:8080`
+fn host_of(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest.split(['/', ':']).next()?;
+    Some(host.to_string()).filter(|x| !x.is_empty())
+}
+
+fn version_at_least(version: &str, minimum: &[u32]) -> bool {
+    let parts: Vec<u32> = version
+        .split(|c: char| !c.is_ascii_digit())
+        .take(minimum.len())
+        .map(|x| x.parse().unwrap_or_default())
+        .collect();
+    parts.as_slice() >= minimum
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{host_of, version_at_least};
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(
+            host_of("https://gitlab.com/").as_deref(),
+            Some("gitlab.com")
+        );
+        assert_eq!(
+            host_of("http://192.168.31.162:8080").as_deref(),
+            Some("192.168.31.162")
+        );
+        assert_eq!(host_of("").as_deref(), None);
+    }
+
+    #[test]
+    fn test_version_at_least() {
+        assert!(version_at_least("2.39.2", &[2, 20]));
+        assert!(version_at_least("2.20.0.windows.1", &[2, 20]));
+        assert!(!version_at_least("2.17.1", &[2, 20]));
+        assert!(version_at_least("18.12.0", &[16]));
+    }
+}
@@ -0,0 +1,246 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use inquire::Confirm;
+use serde_json::Value;
+use std::{env, fs, path::Path};
+
+use crate::{
+    answers::{ask_text, validate_project_name},
+    api::{NewProject, YooApi},
+    loading::loading,
+    metadata::{Metadata, ProjectInfo, METADATA_FILE},
+    output::emit,
+    project::resolve_project,
+    remote::remote_options,
+    Cli,
+};
+
+#[derive(Args)]
+pub(crate) struct InitArgs {
+    /// The project name, defaults to the name of the current directory
+    #[arg(long)]
+    name: Option<String>,
+
+    /// The project description
+    #[arg(long)]
+    description: Option<String>,
+
+    /// The build command of the project
+    #[arg(long)]
+    build_cmd: Option<String>,
+
+    /// The dist of the project
+    #[arg(long)]
+    dist: Option<String>,
+
+    /// Point the remote origin at the repo of the registered project
+    #[arg(long)]
+    rewrite_remote: bool,
+
+    /// Don't push the current branch to the remote origin
+    #[arg(long)]
+    no_push: bool,
+
+    /// Push all the local branches instead of only the current one
+    #[arg(long, conflicts_with = "no_push")]
+    all_branches: bool,
+
+    /// Never prompt, use the detected values for the missing answers
+    #[arg(short, long, visible_alias = "non-interactive")]
+    yes: bool,
+}
+
+// register the repo in the current directory as a project on the server
+pub(crate) fn init(cli: &Cli, args: InitArgs) -> Result<()> {
+    let interactive = !args.yes;
+    let git_repo = git::open_repo(".")?;
+    let root = Path::new(".");
+
+    let mut metadata = Metadata::load(root)?.unwrap_or_default();
+    // a registered repo is not registered again, only the remote and the push are retried
+    let registered = metadata.project.as_ref().map(|x| x.id);
+    let project = match registered {
+        Some(id) => {
+            let project = resolve_project(cli, Some(&id.to_string()))?;
+            tracing::info!(
+                "The repo is already registered as the project {} ({}), skip the registration",
+                project.name,
+                project.id
+            );
+            project
+        }
+        None => {
+            let detected = Detected::from_dir(root)?;
+
+            let project_name = ask_text(
+                args.name,
+                "name",
+                "Please enter the project name:",
+                detected.name.as_deref(),
+                interactive,
+                validate_project_name,
+            )?;
+
+            let description = ask_text(
+                args.description,
+                "description",
+                "Please enter the project description:",
+                Some(detected.description.as_deref().unwrap_or_default()),
+                interactive,
+                |_| Ok(()),
+            )?;
+
+            let build_cmd = ask_text(
+                args.build_cmd,
+                "build-cmd",
+                "Please enter the build command of the project:",
+                Some(&detected.build_cmd),
+                interactive,
+                |_| Ok(()),
+            )?;
+
+            let dist = ask_text(
+                args.dist,
+                "dist",
+                "Please enter the dist of the project:",
+                Some(&detected.dist),
+                interactive,
+                |_| Ok(()),
+            )?;
+
+            let pb = loading("Registering the project")?;
+            let project = YooApi::new(cli).create_project(&NewProject {
+                name: &project_name,
+                description: &description,
+                build_cmd: &build_cmd,
+                dist: &dist,
+            })?;
+            pb.finish_and_clear();
+            tracing::info!(
+                "Successfully registered the project {} ({})",
+                project.name,
+                project.id
+            );
+
+            metadata.project = Some(ProjectInfo::new(&project, cli.server.as_ref().unwrap()));
+            metadata.save(root)?;
+            tracing::info!("Successfully wrote the {}", METADATA_FILE);
+            project
+        }
+    };
+
+    // an existing origin is only replaced when the user agrees
+    let current = git_repo.remote_url("origin");
+    let rewrite =
+        if current.is_none() || current.as_deref() == Some(&project.ssh_url) || args.rewrite_remote
+        {
+            true
+        } else if interactive {
+            Confirm::new(&format!(
+                "Do you want to point the remote origin at {}?",
+                project.ssh_url
+            ))
+            .with_default(true)
+            .prompt()
+            .with_context(|| "Failed to interact with the user")?
+        } else {
+            false
+        };
+
+    let mut pushed = vec![];
+    if rewrite {
+        if current.as_deref() != Some(&project.ssh_url) {
+            git_repo.set_remote(&project.ssh_url)?;
+            tracing::info!("Successfully set the remote origin: {}", project.ssh_url);
+        }
+
+        if !args.no_push {
+            // the other local branches may be private, they are pushed only when asked
+            let branches = if args.all_branches {
+                git_repo.list_branches()?
+            } else {
+                match git_repo.current_branch()? {
+                    Some(branch) if git_repo.list_branches()?.contains(&branch) => vec![branch],
+                    _ => {
+                        tracing::warn!(
+                            "There is no current branch to push, pass `--all-branches` to push all the local branches"
+                        );
+                        vec![]
+                    }
+                }
+            };
+            for branch in branches {
+                let pb = loading(&format!("Pushing {}", branch))?;
+                git_repo.push(&branch, &remote_options(cli).with_progress(pb.transfer()))?;
+                pb.finish_and_clear();
+                tracing::info!("Successfully pushed the branch {}", branch);
+                pushed.push(branch);
+            }
+        }
+    }
+
+    if registered.is_none() {
+        tracing::info!(
+            "Now the project is registered, commit the {} to share it with your team",
+            METADATA_FILE
+        );
+    }
+
+    let output = serde_json::json!({
+        "project": project,
+        "registered": registered.is_none(),
+        "remote": rewrite.then_some(&project.ssh_url),
+        "pushed": pushed,
+    });
+    emit(cli.output, &output, || Ok(()))
+}
+
+/// The answers guessed from the files of the repo
+struct Detected {
+    name: Option<String>,
+    description: Option<String>,
+    build_cmd: String,
+    dist: String,
+}
+
+impl Detected {
+    fn from_dir(dir: &Path) -> Result<Detected> {
+        let name = env::current_dir()
+            .with_context(|| "Failed to get the current directory")?
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| validate_project_name(name).is_ok())
+            .map(|name| name.to_string());
+
+        let package: Option<Value> = fs::read_to_string(dir.join("package.json"))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok());
+
+        let description = package
+            .as_ref()
+            .and_then(|package| package["description"].as_str())
+            .map(|description| description.to_string());
+
+        // use the package manager of the lock file
+        let build_cmd = if dir.join("pnpm-lock.yaml").exists() {
+            "pnpm build"
+        } else if dir.join("package-lock.json").exists() {
+            "npm run build"
+        } else {
+            "yarn build"
+        };
+
+        let dist = if dir.join("vite.config.ts").exists() || dir.join("vite.config.js").exists() {
+            "dist"
+        } else {
+            "build"
+        };
+
+        Ok(Detected {
+            name,
+            description,
+            build_cmd: build_cmd.to_string(),
+            dist: dist.to_string(),
+        })
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{remove_file, rename, File},
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+use crate::{
+    api::Project,
+    manifest::{Hook, RenderContext},
+    CACHE_DIR, JOURNAL_FILE,
+};
+
+/// The steps of `create`, in the order they are executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Step {
+    CreateDir,
+    CloneTemplate,
+    RenderTemplate,
+    RunHook(usize),
+    RegisterProject,
+    WriteMetadata,
+    CommitProject,
+    RewriteRemote,
+    PushMaster,
+    CheckoutDev,
+    PushDev,
+}
+
+/// The journal of a `create` run, it records the answers of the user and the finished steps
+/// so that a failed run can be rolled back and a crashed run can be resumed
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Journal {
+    pub project_name: String,
+    pub project_dir: PathBuf,
+    pub description: String,
+    pub build_cmd: String,
+    pub dist: String,
+    pub template_name: String,
+    /// The git url or the local path of the template
+    pub template_repo: String,
+    #[serde(default)]
+    pub template_ref: Option<String>,
+    #[serde(default)]
+    pub template_subdir: Option<String>,
+    /// The commit of the template once it is checked out
+    #[serde(default)]
+    pub template_commit: Option<String>,
+    /// The values of the template variables and options given by the flags or the answers file
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    /// The values the template is rendered with, they are kept once the user answered them
+    #[serde(default)]
+    pub context: Option<RenderContext>,
+    /// The hooks of the template which are enabled for the context
+    #[serde(default)]
+    pub hooks: Vec<Hook>,
+    pub project: Option<Project>,
+    pub steps: Vec<Step>,
+}
+
+impl Journal {
+    /// load the unfinished journal if there is one
+    pub fn load() -> Result<Option<Journal>> {
+        let journal_file = journal_file()?;
+        if !journal_file.exists() {
+            return Ok(None);
+        }
+
+        let file = File::open(journal_file).with_context(|| "Failed to open the journal")?;
+        let journal = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| "Failed to parse the journal")?;
+
+        Ok(Some(journal))
+    }
+
+    /// persist the journal, it is written to a temporary file which replaces the journal
+    /// so that a crash in the middle leaves the previous journal behind
+    pub fn save(&self) -> Result<()> {
+        let journal_file = journal_file()?;
+        let temp_file = journal_file.with_file_name(format!("{}.tmp", JOURNAL_FILE));
+
+        let mut writer = BufWriter::new(
+            File::create(&temp_file).with_context(|| "Failed to write the journal")?,
+        );
+        serde_json::to_writer_pretty(&mut writer, self)
+            .with_context(|| "Failed to write the journal")?;
+        writer
+            .into_inner()
+            .map_err(|err| err.into_error())
+            .and_then(|file| file.sync_all())
+            .with_context(|| "Failed to write the journal")?;
+
+        rename(&temp_file, &journal_file).with_context(|| "Failed to write the journal")?;
+        Ok(())
+    }
+
+    pub fn discard() -> Result<()> {
+        let journal_file = journal_file()?;
+        if journal_file.exists() {
+            remove_file(journal_file).with_context(|| "Failed to remove the journal")?;
+        }
+        Ok(())
+    }
+
+    pub fn is_done(&self, step: Step) -> bool {
+        self.steps.contains(&step)
+    }
+
+    /// mark the step as finished and persist the journal immediately
+    pub fn record(&mut self, step: Step) -> Result<()> {
+        self.steps.push(step);
+        self.save()
+    }
+}
+
+fn journal_file() -> Result<PathBuf> {
+    let home_dir = dirs::home_dir().with_context(|| "Failed to get the home dir")?;
+    Ok(home_dir.join(format!("{}/{}", CACHE_DIR, JOURNAL_FILE)))
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{Journal, Step};
+
+    #[test]
+    fn test_journal_roundtrip() {
+        let journal = Journal {
+            project_name: "demo".to_string(),
+            project_dir: "/tmp/demo".into(),
+            description: "a demo project".to_string(),
+            build_cmd: "yarn build".to_string(),
+            dist: "build".to_string(),
+            template_name: "react".to_string(),
+            template_repo: "ssh://git@example.com/yoo/react.git".to_string(),
+            template_ref: None,
+            template_subdir: None,
+            template_commit: None,
+            vars: Default::default(),
+            context: None,
+            hooks: vec![],
+            project: None,
+            steps: vec![Step::CreateDir, Step::CloneTemplate, Step::RunHook(0)],
+        };
+
+        let json = serde_json::to_string(&journal).unwrap();
+        assert!(json.contains("\"clone_template\""));
+
+        let journal: Journal = serde_json::from_str(&json).unwrap();
+        assert!(journal.is_done(Step::CloneTemplate));
+        assert!(journal.is_done(Step::RunHook(0)));
+        assert!(!journal.is_done(Step::RunHook(1)));
+        assert!(!journal.is_done(Step::RegisterProject));
+    }
+}
@@ -1,20 +1,49 @@
 use anyhow::{Context, Result};
+use auth::AuthCommands;
 use clap::{Parser, Subcommand};
-use create::{login, read_authorization, write_authorization};
+use clone::CloneArgs;
+use config::{Config, ConfigCommands};
+use create::CreateArgs;
+use doctor::DoctorArgs;
+use init::InitArgs;
 use once_cell::sync::Lazy;
-use reqwest::StatusCode;
-use std::env;
+use output::{emit, ErrorOutput, OutputFormat};
+use profile::ProfileCommands;
+use project::ProjectCommands;
+use std::{env, path::PathBuf};
+use template::TemplateCommands;
+use terminal::ColorChoice;
 use tracing::metadata::LevelFilter;
 use tracing_subscriber::{prelude::__tracing_subscriber_SubscriberExt, Layer};
-
-use crate::loading::loading;
-
+use upgrade::UpgradeArgs;
+
+mod answers;
+mod api;
+mod auth;
+mod cache;
+mod clone;
+mod config;
 mod create;
+mod credentials;
+mod doctor;
+mod init;
+mod journal;
 mod loading;
+mod manifest;
+mod metadata;
+mod output;
+mod profile;
+mod project;
+mod remote;
 mod submit;
+mod template;
+mod terminal;
+mod upgrade;
 
 pub const CACHE_DIR: &str = ".yoo";
 pub const CACHE_FILE: &str = "cache.json";
+pub const JOURNAL_FILE: &str = "journal.json";
+pub const TEMPLATES_DIR: &str = "templates";
 
 // lazy to initialize the reqwest client
 static REQUEST: Lazy<reqwest::blocking::Client> = Lazy::new(reqwest::blocking::Client::new);
@@ -47,112 +76,89 @@ struct Cli {
     #[arg(long)]
     server_password: Option<String>,
 
-    project_id: Option<i32>,
+    /// The profile of the server and the account to use, defaults to YOO_PROFILE
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// How to print the result of the command, the logs always go to stderr
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Human)]
+    output: OutputFormat,
+
+    /// When to color the output, NO_COLOR is respected by default
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
 
-    project_name: Option<String>,
+    /// Only print the warnings, the errors and the result, no progress
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// The settings merged from the flags, the env and the config files
+    #[arg(skip)]
+    config: Config,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Create a new project based on the template
-    Create {},
+    Create(CreateArgs),
+    /// Log in to the server and manage the cached token
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommands,
+    },
+    /// Clone a project registered on the server
+    Clone(CloneArgs),
+    /// Register the existing repo in the current directory on the server
+    Init(InitArgs),
+    /// Manage the project of the current repo
+    Project {
+        #[command(subcommand)]
+        command: ProjectCommands,
+    },
+    /// Manage the project templates
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommands,
+    },
     /// Submit the repo to the resource server
     Submit {
         /// Specify the branch to submit
         #[arg(long)]
         branch: Option<String>,
     },
-}
-
-impl Cli {
-    fn delete_project(&self) -> Result<()> {
-        if self.project_id.is_none() {
-            tracing::debug!("Project id is none");
-            return Ok(());
-        }
-
-        let mut authorization = match read_authorization() {
-            Ok(auth) => auth,
-            Err(_) => {
-                let auth = login(
-                    self.server.as_ref().unwrap(),
-                    self.server_email.as_ref().unwrap(),
-                    self.server_password.as_ref().unwrap(),
-                )?;
-                write_authorization(&auth)?;
-                auth
-            }
-        };
-
-        let resp = delete_project_from_server(self, &authorization)?;
-
-        match resp.status() {
-            StatusCode::OK => Ok(()),
-            StatusCode::BAD_REQUEST => {
-                authorization = login(
-                    self.server.as_ref().unwrap(),
-                    self.server_email.as_ref().unwrap(),
-                    self.server_password.as_ref().unwrap(),
-                )?;
-
-                write_authorization(&authorization)?;
-
-                let resp = delete_project_from_server(self, &authorization)?;
-
-                if resp.status() != StatusCode::OK {
-                    return Err(anyhow::Error::msg("Failed to delete the project"));
-                }
-
-                Ok(())
-            }
-            _ => Err(anyhow::Error::msg("Failed to delete the project"))?,
-        }
-
-        // Ok(())
-    }
-
-    fn delete_local_repo(&self) -> Result<()> {
-        if self.project_name.is_none() {
-            return Ok(());
-        }
-        // get working dir
-        let working_dir =
-            env::current_dir().with_context(|| "Failed to get the current directory")?;
-        // get the repo dir
-        let repo_dir = working_dir.join(self.project_name.clone().unwrap());
-        // remove the repo dir
-        std::fs::remove_dir_all(repo_dir).with_context(|| "Failed to remove the repo dir")?;
-        Ok(())
-    }
-}
-
-fn delete_project_from_server(
-    cli: &Cli,
-    authorization: &String,
-) -> Result<reqwest::blocking::Response, anyhow::Error> {
-    let resp = REQUEST
-        .delete(format!("/v1/projects/{}", cli.project_id.unwrap()))
-        .header("Authorization", authorization)
-        .send()
-        .with_context(|| "Failed to delete the project")?;
-    Ok(resp)
+    /// Merge the latest changes of the template into the project
+    Upgrade(UpgradeArgs),
+    /// Manage the profiles of the servers and the accounts
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+    /// Manage the settings in ~/.yoo/config.toml and the yoo.toml of the project
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Check the environment, the config and the credentials
+    Doctor(DoctorArgs),
 }
 
 /// init the cli
 pub fn init() -> Result<()> {
-    loading("Initializing...")?.finish_and_clear();
-
-    let mut cli = Cli::parse();
+    let cli = Cli::parse();
 
     let level_filter = if cli.debug {
         LevelFilter::DEBUG
+    } else if cli.quiet {
+        LevelFilter::WARN
     } else {
         LevelFilter::INFO
     };
 
-    let enable_ansi = true;
+    // no spinners and colors in the CI logs and the pipes
+    let enable_ansi = terminal::setup(cli.color, cli.quiet);
 
     let layer = tracing_subscriber::fmt::Layer::new()
+        .with_writer(std::io::stderr)
         .with_ansi(enable_ansi)
         .with_filter(level_filter);
 
@@ -164,67 +170,101 @@ pub fn init() -> Result<()> {
     if cli.debug {
         tracing::info!("CLI is running in debug mode");
     }
+
+    // the scripts get the error as a json object too
+    let output = cli.output;
+    let result = run(cli);
+    if let Err(err) = &result {
+        if !output.is_human() {
+            emit(output, &ErrorOutput::new(err), || Ok(()))?;
+        }
+    }
+    result
+}
+
+// run the command of the cli
+fn run(mut cli: Cli) -> Result<()> {
     // create cache dir and cache file
     create_cache_file()?;
 
+    // the profiles and the settings are managed without a server, the doctor checks it itself
+    match cli.command.take() {
+        Some(Commands::Profile { command }) => return profile::profile(&cli, command),
+        Some(Commands::Config { command }) => return config::config(&cli, command),
+        Some(Commands::Doctor(args)) => return doctor::doctor(cli, args),
+        command => cli.command = command,
+    }
+
     // check the configuration
     check_config(&mut cli)?;
 
     // print the configuration
     tracing::info!("Your configuration is as follows: ");
+    if let Some(profile) = &cli.profile {
+        tracing::info!("PROFILE: {}", profile);
+    }
     tracing::info!("SERVER_URL: {}", cli.server.clone().unwrap());
-    tracing::info!("SERVER_USERNAME: {}", cli.server_email.clone().unwrap());
+    if let Some(email) = &cli.server_email {
+        tracing::info!("SERVER_EMAIL: {}", email);
+    }
     // tracing::info!("SERVER_PASSWORD: {}", cli.server_password.clone().unwrap());
 
-    match cli.command {
-        Some(Commands::Create {}) => match create::create(&mut cli) {
+    match cli.command.take() {
+        Some(Commands::Create(args)) => match create::create(&cli, args) {
             Ok(_) => Ok(()),
-            Err(err) => {
+            // the error is only logged for humans unless in debug mode
+            Err(err) if cli.output.is_human() && !cli.debug => {
                 tracing::error!("Failed to create the repo: {}", err);
-                let pb = loading("Cleaning up...")?;
-                // try to delete the remote repo
-                cli.delete_project()?;
-                // try to delete the local repo
-                cli.delete_local_repo()?;
-
-                pb.finish_and_clear();
-                tracing::info!("Successfully cleaned up");
-                if cli.debug {
-                    Err(err)
-                } else {
-                    Ok(())
-                }
+                Ok(())
             }
+            Err(err) => Err(err),
         },
-        Some(Commands::Submit { ref branch }) => submit::submit(&cli, branch.clone()),
-        None => Ok(()),
+        Some(Commands::Auth { command }) => auth::auth(&cli, command),
+        Some(Commands::Clone(args)) => clone::clone(&cli, args),
+        Some(Commands::Init(args)) => init::init(&cli, args),
+        Some(Commands::Project { command }) => project::project(&cli, command),
+        Some(Commands::Template { command }) => template::template(&cli, command),
+        Some(Commands::Submit { branch }) => submit::submit(&cli, branch),
+        Some(Commands::Upgrade(args)) => upgrade::upgrade(&cli, args),
+        Some(Commands::Profile { .. } | Commands::Config { .. } | Commands::Doctor(_)) | None => {
+            Ok(())
+        }
     }
 }
 
-// check the environment variable configuration
+// merge the flags, the env and the config files, see `yoo config explain`
 fn check_config(cli: &mut Cli) -> Result<()> {
-    // if the cli doesn't config the SERVER_URL, check the env
-    if cli.server.is_none() {
-        let server_url = env::var("YOO_SERVER").with_context(|| "SERVER is not set")?;
-        cli.server = Some(server_url);
-    }
+    let config = Config::resolve(cli)?;
+
+    cli.server = Some(
+        config
+            .get("server")
+            .with_context(|| {
+                "SERVER is not set, set YOO_SERVER, run `yoo config set server` or add a profile with `yoo profile add`"
+            })?
+            .to_string(),
+    );
+    // the credentials are optional, the password is prompted when it is needed
+    cli.server_email = config.get("email").map(|x| x.to_string());
+    cli.profile = config.get("profile").map(|x| x.to_string());
+    cli.config = config;
 
-    // check username
-    if cli.server_email.is_none() {
-        let username = env::var("YOO_SERVER_EMAIL").with_context(|| "SERVER_EMAIL is not set")?;
-        cli.server_email = Some(username);
-    }
-
-    // check password
     if cli.server_password.is_none() {
-        let password =
-            env::var("YOO_SERVER_PASSWORD").with_context(|| "SERVER_PASSWORD is not set")?;
-        cli.server_password = Some(password);
+        cli.server_password = env::var("YOO_SERVER_PASSWORD").ok();
     }
 
     Ok(())
 }
 
+/// expand the `~/` of a path to the home dir, the shell doesn't expand it in the settings
+/// and after `=` in the flags
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
 fn create_cache_file() -> Result<()> {
     let home_dir = dirs::home_dir().with_context(|| "Failed to get home dir")?;
     let cache_dir = home_dir.join(CACHE_DIR);
@@ -241,3 +281,15 @@ fn create_cache_file() -> Result<()> {
 
     Ok(())
 }
+
+// test
+#[cfg(test)]
+mod test {
+    use super::Cli;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+}
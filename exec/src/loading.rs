@@ -1,25 +1,189 @@
 use anyhow::Result;
-use std::time::Duration;
+use console::Term;
+use git::TransferProgress;
+use once_cell::sync::OnceCell;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle, WeakProgressBar};
 
-pub(crate) fn loading(tip: &str) -> Result<ProgressBar> {
+/// How the progress of the long steps is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ProgressMode {
+    /// A spinner on the terminal
+    Spinner,
+    /// A log line when the step starts, for the CI logs and the pipes
+    Plain,
+    /// Nothing, in quiet mode
+    Hidden,
+}
+
+static MODE: OnceCell<ProgressMode> = OnceCell::new();
+
+// the last spinner, a prompt hides it while asking
+static SPINNER: Mutex<Option<WeakProgressBar>> = Mutex::new(None);
+
+// For more spinners check out the cli-spinners project:
+// https://github.com/sindresorhus/cli-spinners/blob/master/spinners.json
+const TICKS: &[&str] = &[
+    "[    ]", "[=   ]", "[==  ]", "[=== ]", "[ ===]", "[  ==]", "[   =]", "[    ]", "[   =]",
+    "[  ==]", "[ ===]", "[====]", "[=== ]", "[==  ]", "[=   ]",
+];
+
+/// set how the progress is shown, it can only be set once before the first step
+pub(crate) fn set_mode(mode: ProgressMode) {
+    let _ = MODE.set(mode);
+}
+
+/// run a prompt with the spinner hidden, the ticks of the spinner would garble it
+pub(crate) fn suspend<R>(prompt: impl FnOnce() -> R) -> R {
+    let spinner = SPINNER.lock().unwrap().as_ref().and_then(|x| x.upgrade());
+    match spinner {
+        Some(spinner) if !spinner.is_finished() => spinner.suspend(prompt),
+        _ => prompt(),
+    }
+}
+
+/// print a line of a command above the spinner, nothing is printed in quiet mode
+pub(crate) fn println(line: &str) {
+    if mode() != ProgressMode::Hidden {
+        suspend(|| eprintln!("{}", line));
+    }
+}
+
+fn mode() -> ProgressMode {
+    *MODE.get_or_init(|| {
+        if Term::stderr().is_term() {
+            ProgressMode::Spinner
+        } else {
+            ProgressMode::Plain
+        }
+    })
+}
+
+/// The progress of a long step, a spinner in a terminal and a log line elsewhere
+pub(crate) struct Progress {
+    bar: Option<ProgressBar>,
+    tip: String,
+    started: Instant,
+}
+
+impl Progress {
+    pub fn finish_and_clear(&self) {
+        match &self.bar {
+            Some(bar) => bar.finish_and_clear(),
+            None if mode() == ProgressMode::Plain => {
+                tracing::debug!("{} took {:.1?}", self.tip, self.started.elapsed())
+            }
+            None => {}
+        }
+    }
+
+    /// report the transfer of a remote, the spinner becomes a bar once the total is known
+    pub fn transfer(&self) -> git::ProgressCallback {
+        let bar = self.bar.clone();
+        let tip = self.tip.clone();
+        let started = Instant::now();
+        // the phase and the quarter which are logged last in plain mode
+        let logged = Mutex::new(("", 0));
+
+        Arc::new(move |progress| {
+            let (phase, current, total, bytes) = describe(progress);
+            let mut message = format!("{}: {}", tip, phase);
+            if let Some(bytes) = bytes {
+                let elapsed = started.elapsed().as_secs_f64().max(0.001);
+                message.push_str(&format!(
+                    ", {}, {}/s",
+                    HumanBytes(bytes as u64),
+                    HumanBytes((bytes as f64 / elapsed) as u64)
+                ));
+            }
+
+            match &bar {
+                Some(bar) if total > 0 => {
+                    if bar.length() != Some(total as u64) {
+                        bar.set_style(bar_style());
+                        bar.set_length(total as u64);
+                    }
+                    bar.set_position(current as u64);
+                    bar.set_message(message);
+                }
+                Some(bar) => bar.set_message(message),
+                None if mode() == ProgressMode::Plain && total > 0 => {
+                    // a line for every quarter of a phase is enough for the logs
+                    let quarter = current * 4 / total;
+                    let mut logged = logged.lock().unwrap();
+                    if *logged != (phase, quarter) {
+                        *logged = (phase, quarter);
+                        tracing::info!("{} ({}/{})", message, current, total);
+                    }
+                }
+                None => {}
+            }
+        })
+    }
+}
+
+// the phase, the finished and the total objects and the transferred bytes
+fn describe(progress: TransferProgress) -> (&'static str, usize, usize, Option<usize>) {
+    match progress {
+        TransferProgress::Receiving {
+            received,
+            total,
+            bytes,
+            ..
+        } => ("receiving objects", received, total, Some(bytes)),
+        TransferProgress::Resolving { resolved, total } => {
+            ("resolving deltas", resolved, total, None)
+        }
+        TransferProgress::Packing { current, total } => ("packing objects", current, total, None),
+        TransferProgress::Sending {
+            current,
+            total,
+            bytes,
+        } => ("sending objects", current, total, bytes),
+    }
+}
+
+fn bar_style() -> ProgressStyle {
+    ProgressStyle::with_template("{spinner:.blue} [{bar:30.cyan/blue}] {pos}/{len} {msg}")
+        .unwrap()
+        .tick_strings(TICKS)
+        .progress_chars("=> ")
+}
+
+pub(crate) fn loading(tip: &str) -> Result<Progress> {
+    let bar = match mode() {
+        ProgressMode::Spinner => Some(spinner(tip)),
+        ProgressMode::Plain => {
+            tracing::info!("{}", tip);
+            None
+        }
+        ProgressMode::Hidden => None,
+    };
+
+    Ok(Progress {
+        bar,
+        tip: tip.to_string(),
+        started: Instant::now(),
+    })
+}
+
+fn spinner(tip: &str) -> ProgressBar {
     let pb = ProgressBar::new_spinner();
     pb.enable_steady_tick(Duration::from_millis(120));
     pb.set_style(
         ProgressStyle::with_template("{spinner:.blue} {msg}")
             .unwrap()
-            // For more spinners check out the cli-spinners project:
-            // https://github.com/sindresorhus/cli-spinners/blob/master/spinners.json
-            .tick_strings(&[
-                "[    ]", "[=   ]", "[==  ]", "[=== ]", "[ ===]", "[  ==]", "[   =]", "[    ]",
-                "[   =]", "[  ==]", "[ ===]", "[====]", "[=== ]", "[==  ]", "[=   ]",
-            ]),
+            .tick_strings(TICKS),
     );
 
     pb.set_message(tip.to_string());
+    *SPINNER.lock().unwrap() = Some(pb.downgrade());
 
-    Ok(pb)
+    pb
 }
 
 // test
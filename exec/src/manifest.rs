@@ -0,0 +1,797 @@
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use inquire::{Confirm, Select};
+use minijinja::{Environment, UndefinedBehavior, Value};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
+    fs,
+    io::{BufRead, BufReader},
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
+use walkdir::WalkDir;
+
+use crate::{answers::ask_text, loading};
+
+pub(crate) const MANIFEST_FILE: &str = "yoo-template.toml";
+
+/// The variables which are always available, they come from the project information
+const BUILTIN_VARIABLES: [&str; 4] = ["name", "description", "build_cmd", "dist"];
+
+/// How many lines of the stderr of a failed hook are kept in the error
+const ERROR_LINES: usize = 10;
+
+/// The manifest of a template, it lives in the root of the template repo
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Manifest {
+    /// The glob patterns of the files whose content is rendered, no file is rendered by default
+    pub files: Vec<String>,
+    /// The glob patterns of the files whose content is never rendered
+    pub exclude: Vec<String>,
+    /// The variables to ask for, in order
+    pub variables: Vec<Variable>,
+    /// The optional features of the template, in order
+    pub options: Vec<TemplateOption>,
+    /// The commands to run once the template is rendered, in order
+    pub hooks: Vec<Hook>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Variable {
+    pub name: String,
+    pub prompt: Option<String>,
+    /// The default value, it is rendered so it can refer to the variables before it
+    pub default: Option<String>,
+}
+
+/// A boolean option, or a choice option when it has choices
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TemplateOption {
+    pub name: String,
+    pub prompt: Option<String>,
+    pub default: Option<OptionValue>,
+    /// The paths which are removed when the boolean option is off
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// The paths which are removed when the boolean option is on
+    #[serde(default)]
+    pub off_paths: Vec<String>,
+    #[serde(default)]
+    pub choices: Vec<Choice>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Choice {
+    pub value: String,
+    /// The paths which are removed when another choice is selected
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum OptionValue {
+    Bool(bool),
+    Choice(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Hook {
+    /// The message shown while the hook is running
+    pub name: String,
+    /// The command to run in the project directory
+    pub run: String,
+    /// An expression on the variables and options, the hook only runs when it is true
+    #[serde(default, skip_serializing)]
+    pub when: Option<String>,
+    /// The command to run in the project directory when the creation is rolled back
+    pub undo: Option<String>,
+}
+
+/// The values the template is rendered with
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct RenderContext {
+    pub vars: BTreeMap<String, String>,
+    pub options: BTreeMap<String, OptionValue>,
+}
+
+impl RenderContext {
+    /// a context with the built-in variables
+    pub fn new(name: &str, description: &str, build_cmd: &str, dist: &str) -> RenderContext {
+        let vars = BUILTIN_VARIABLES
+            .iter()
+            .zip([name, description, build_cmd, dist])
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        RenderContext {
+            vars,
+            options: BTreeMap::new(),
+        }
+    }
+
+    fn values(&self) -> BTreeMap<&str, Value> {
+        let mut values: BTreeMap<&str, Value> = self
+            .vars
+            .iter()
+            .map(|(name, value)| (name.as_str(), Value::from(value.as_str())))
+            .collect();
+
+        for (name, value) in &self.options {
+            let value = match value {
+                OptionValue::Bool(value) => Value::from(*value),
+                OptionValue::Choice(value) => Value::from(value.as_str()),
+            };
+            values.insert(name, value);
+        }
+
+        values
+    }
+}
+
+impl Manifest {
+    /// read the manifest in the template dir if there is one
+    pub fn load(dir: &Path) -> Result<Option<Manifest>> {
+        let manifest_file = dir.join(MANIFEST_FILE);
+        if !manifest_file.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&manifest_file)
+            .with_context(|| format!("Failed to read the {}", MANIFEST_FILE))?;
+        let manifest = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse the {}", MANIFEST_FILE))?;
+
+        Ok(Some(manifest))
+    }
+
+    /// add the values of the declared variables and options to the context, the given values
+    /// are used first and the missing ones are prompted for
+    pub fn ask(
+        &self,
+        context: &mut RenderContext,
+        given: &BTreeMap<String, String>,
+        interactive: bool,
+    ) -> Result<()> {
+        let env = environment();
+
+        for variable in &self.variables {
+            let default = match &variable.default {
+                Some(default) => {
+                    Some(env.render_str(default, context.values()).with_context(|| {
+                        format!("Failed to render the default of {}", variable.name)
+                    })?)
+                }
+                None => None,
+            };
+
+            let message = match &variable.prompt {
+                Some(prompt) => prompt.clone(),
+                None => format!("Please enter the {}:", variable.name),
+            };
+
+            let value = ask_text(
+                given.get(&variable.name).cloned(),
+                &format!("var {}=<value>", variable.name),
+                &message,
+                default.as_deref(),
+                interactive,
+                |_| Ok(()),
+            )?;
+
+            context.vars.insert(variable.name.clone(), value);
+        }
+
+        for option in &self.options {
+            let value = option.ask(given.get(&option.name), interactive)?;
+            context.options.insert(option.name.clone(), value);
+        }
+
+        Ok(())
+    }
+
+    /// find the mistakes which would only show up when a project is created
+    pub fn lint(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let identifier = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+        let env = environment();
+
+        let mut names = HashSet::new();
+        let declared = self
+            .variables
+            .iter()
+            .map(|x| &x.name)
+            .chain(self.options.iter().map(|x| &x.name));
+        for name in declared {
+            if !identifier.is_match(name) {
+                problems.push(format!("`{}` is not a valid variable name", name));
+            }
+            if BUILTIN_VARIABLES.contains(&name.as_str()) {
+                problems.push(format!("`{}` is a built-in variable", name));
+            }
+            if !names.insert(name) {
+                problems.push(format!("`{}` is declared more than once", name));
+            }
+        }
+
+        let mut patterns: Vec<&String> = self.files.iter().chain(&self.exclude).collect();
+
+        for option in &self.options {
+            patterns.extend(&option.paths);
+            patterns.extend(&option.off_paths);
+
+            if option.choices.is_empty() {
+                if let Some(OptionValue::Choice(_)) = option.default {
+                    problems.push(format!(
+                        "The default of the option {} must be a boolean",
+                        option.name
+                    ));
+                }
+                continue;
+            }
+
+            if !option.paths.is_empty() || !option.off_paths.is_empty() {
+                problems.push(format!(
+                    "The option {} has choices, its paths must be declared on the choices",
+                    option.name
+                ));
+            }
+
+            let mut values = HashSet::new();
+            for choice in &option.choices {
+                patterns.extend(&choice.paths);
+                if !values.insert(&choice.value) {
+                    problems.push(format!(
+                        "The choice {} of the option {} is declared more than once",
+                        choice.value, option.name
+                    ));
+                }
+            }
+
+            match &option.default {
+                Some(OptionValue::Choice(default)) if !values.contains(default) => {
+                    problems.push(format!(
+                        "The default of the option {} must be one of its choices",
+                        option.name
+                    ))
+                }
+                Some(OptionValue::Bool(_)) => problems.push(format!(
+                    "The default of the option {} must be one of its choices",
+                    option.name
+                )),
+                _ => {}
+            }
+        }
+
+        for pattern in patterns {
+            if let Err(err) = Glob::new(pattern) {
+                problems.push(format!("Invalid glob pattern {}: {}", pattern, err));
+            }
+        }
+
+        for hook in &self.hooks {
+            if let Some(when) = &hook.when {
+                if let Err(err) = env.compile_expression(when) {
+                    problems.push(format!(
+                        "Invalid condition of the hook {}: {}",
+                        hook.name, err
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// the hooks whose condition holds for the context
+    pub fn hooks(&self, context: &RenderContext) -> Result<Vec<Hook>> {
+        let env = environment();
+        let mut hooks = vec![];
+
+        for hook in &self.hooks {
+            let enabled = match &hook.when {
+                Some(when) => env
+                    .compile_expression(when)
+                    .and_then(|expr| expr.eval(context.values()))
+                    .with_context(|| format!("Failed to evaluate the condition of {}", hook.name))?
+                    .is_true(),
+                None => true,
+            };
+
+            if enabled {
+                hooks.push(hook.clone());
+            }
+        }
+
+        Ok(hooks)
+    }
+
+    /// remove the paths of the disabled options, render the template and remove the manifest
+    pub fn apply(&self, dir: &Path, context: &RenderContext) -> Result<()> {
+        self.remove_disabled_paths(dir, context)?;
+        self.render(dir, context)?;
+        fs::remove_file(dir.join(MANIFEST_FILE))
+            .with_context(|| format!("Failed to remove the {}", MANIFEST_FILE))
+    }
+
+    fn remove_disabled_paths(&self, dir: &Path, context: &RenderContext) -> Result<()> {
+        let mut patterns = vec![];
+        for option in &self.options {
+            match context.options.get(&option.name) {
+                Some(OptionValue::Bool(true)) => patterns.extend(&option.off_paths),
+                Some(OptionValue::Bool(false)) => patterns.extend(&option.paths),
+                Some(OptionValue::Choice(value)) => option
+                    .choices
+                    .iter()
+                    .filter(|choice| &choice.value != value)
+                    .for_each(|choice| patterns.extend(&choice.paths)),
+                None => {}
+            }
+        }
+
+        if patterns.is_empty() {
+            return Ok(());
+        }
+
+        let disabled = glob_set(patterns)?;
+        let mut entries = WalkDir::new(dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != ".git");
+
+        while let Some(entry) = entries.next() {
+            let entry = entry.with_context(|| "Failed to walk through the template")?;
+            let path = entry.path();
+            let relative = path
+                .strip_prefix(dir)
+                .with_context(|| "Failed to get the relative path")?;
+
+            if !disabled.is_match(relative) {
+                continue;
+            }
+
+            if entry.file_type().is_dir() {
+                fs::remove_dir_all(path)
+                    .with_context(|| format!("Failed to remove {}", relative.display()))?;
+                entries.skip_current_dir();
+            } else {
+                fs::remove_file(path)
+                    .with_context(|| format!("Failed to remove {}", relative.display()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// render the content of the matched files and the names of all the files and directories
+    pub fn render(&self, dir: &Path, context: &RenderContext) -> Result<()> {
+        let env = environment();
+        let values = context.values();
+        let files = glob_set(&self.files)?;
+        let exclude = glob_set(&self.exclude)?;
+
+        let mut entries = WalkDir::new(dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != ".git")
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| "Failed to walk through the template")?;
+        // the children come before their parent so renaming a directory never invalidates the paths
+        entries.reverse();
+
+        for entry in entries {
+            let path = entry.path();
+            let relative = path
+                .strip_prefix(dir)
+                .with_context(|| "Failed to get the relative path")?;
+
+            if entry.file_type().is_file()
+                && relative != Path::new(MANIFEST_FILE)
+                && files.is_match(relative)
+                && !exclude.is_match(relative)
+            {
+                render_file(&env, path, &values)
+                    .with_context(|| format!("Failed to render {}", relative.display()))?;
+            }
+
+            let name = entry.file_name().to_string_lossy();
+            if name.contains("{{") {
+                let new_name = env.render_str(&name, &values).with_context(|| {
+                    format!("Failed to render the name of {}", relative.display())
+                })?;
+                fs::rename(path, path.with_file_name(new_name))
+                    .with_context(|| format!("Failed to rename {}", relative.display()))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl TemplateOption {
+    fn ask(&self, given: Option<&String>, interactive: bool) -> Result<OptionValue> {
+        let message = match &self.prompt {
+            Some(prompt) => prompt.clone(),
+            None => format!("Please choose the {}:", self.name),
+        };
+
+        // a boolean option
+        if self.choices.is_empty() {
+            let default = match &self.default {
+                Some(OptionValue::Bool(default)) => *default,
+                Some(OptionValue::Choice(_)) => {
+                    return Err(anyhow::Error::msg(format!(
+                        "The default of the option {} must be a boolean",
+                        self.name
+                    )))
+                }
+                None => false,
+            };
+
+            let value = match given.map(|value| value.to_lowercase()) {
+                Some(value) => match value.as_str() {
+                    "true" | "yes" | "y" | "1" => true,
+                    "false" | "no" | "n" | "0" => false,
+                    _ => {
+                        return Err(anyhow::Error::msg(format!(
+                            "The option {} must be true or false",
+                            self.name
+                        )))
+                    }
+                },
+                None if interactive => Confirm::new(&message)
+                    .with_default(default)
+                    .prompt()
+                    .with_context(|| "Failed to interact with the user")?,
+                None => default,
+            };
+
+            return Ok(OptionValue::Bool(value));
+        }
+
+        let values: Vec<&str> = self.choices.iter().map(|c| c.value.as_str()).collect();
+        let position = |value: &str| {
+            values.iter().position(|v| *v == value).with_context(|| {
+                format!(
+                    "The option {} must be one of {}",
+                    self.name,
+                    values.join(", ")
+                )
+            })
+        };
+
+        let default = match &self.default {
+            Some(OptionValue::Choice(default)) => position(default)?,
+            Some(OptionValue::Bool(_)) => {
+                return Err(anyhow::Error::msg(format!(
+                    "The default of the option {} must be one of its choices",
+                    self.name
+                )))
+            }
+            None => 0,
+        };
+
+        let value = match given {
+            Some(value) => values[position(value)?],
+            None if interactive => Select::new(&message, values.clone())
+                .with_starting_cursor(default)
+                .prompt()
+                .with_context(|| "Failed to interact with the user")?,
+            None => values[default],
+        };
+
+        Ok(OptionValue::Choice(value.to_string()))
+    }
+}
+
+impl Hook {
+    pub fn run(&self, dir: &Path) -> Result<()> {
+        run_command(&self.run, dir).with_context(|| format!("Failed to run the hook {}", self.name))
+    }
+
+    pub fn undo(&self, dir: &Path) -> Result<()> {
+        match &self.undo {
+            Some(undo) if dir.exists() => run_command(undo, dir)
+                .with_context(|| format!("Failed to undo the hook {}", self.name)),
+            _ => Ok(()),
+        }
+    }
+}
+
+// the output of the command is streamed above the spinner,
+// the last lines of its stderr are kept for the error
+fn run_command(command: &str, dir: &Path) -> Result<()> {
+    let mut child = if cfg!(windows) {
+        let mut child = Command::new("cmd");
+        child.arg("/C");
+        child
+    } else {
+        let mut child = Command::new("sh");
+        child.arg("-c");
+        child
+    }
+    .arg(command)
+    .current_dir(dir)
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .with_context(|| format!("Failed to execute `{}`", command))?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .with_context(|| "Failed to read the stdout")?;
+    let stderr = child
+        .stderr
+        .take()
+        .with_context(|| "Failed to read the stderr")?;
+    let printer = thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            loading::println(&line);
+        }
+    });
+
+    let mut tail = VecDeque::new();
+    for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+        loading::println(&line);
+        if tail.len() == ERROR_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+    let _ = printer.join();
+
+    let status = child
+        .wait()
+        .with_context(|| format!("Failed to wait for `{}`", command))?;
+    if status.success() {
+        return Ok(());
+    }
+
+    let mut message = format!("`{}` failed with {}", command, status);
+    for line in tail {
+        message.push('\n');
+        message.push_str(&line);
+    }
+    Err(anyhow::Error::msg(message))
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_keep_trailing_newline(true);
+    env
+}
+
+fn glob_set<'a>(patterns: impl IntoIterator<Item = &'a String>) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder
+            .add(Glob::new(pattern).with_context(|| format!("Invalid glob pattern {}", pattern))?);
+    }
+    builder
+        .build()
+        .with_context(|| "Failed to build the glob patterns")
+}
+
+fn render_file(env: &Environment, path: &Path, values: &BTreeMap<&str, Value>) -> Result<()> {
+    let bytes = fs::read(path)?;
+    // binary files are copied as they are
+    let Ok(content) = String::from_utf8(bytes) else {
+        return Ok(());
+    };
+
+    if !content.contains("{{") && !content.contains("{%") {
+        return Ok(());
+    }
+
+    let rendered = env.render_str(&content, values)?;
+    fs::write(path, rendered)?;
+    Ok(())
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{run_command, Manifest, OptionValue, RenderContext};
+    use std::{collections::BTreeMap, fs};
+
+    #[test]
+    fn test_render() {
+        let dir = std::env::temp_dir().join(format!("yoo-render-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/{{ name }}")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join(".git/config.json"), "{{ name }}").unwrap();
+        fs::write(dir.join("package.json"), "{\"name\": \"{{ name }}\"}\n").unwrap();
+        fs::write(dir.join("README.md"), "# {{ name }}\n").unwrap();
+        fs::write(
+            dir.join("src/{{ name }}/App.jsx"),
+            "<div style={{ color: 1 }} />",
+        )
+        .unwrap();
+
+        let manifest: Manifest = toml::from_str(
+            r#"
+            files = ["*.json"]
+
+            [[variables]]
+            name = "title"
+            default = "{{ name | upper }}"
+            "#,
+        )
+        .unwrap();
+
+        let mut context = RenderContext::default();
+        context.vars.insert("name".to_string(), "demo".to_string());
+        manifest.ask(&mut context, &BTreeMap::new(), false).unwrap();
+        assert_eq!(context.vars["title"], "DEMO");
+
+        manifest.render(&dir, &context).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("package.json")).unwrap(),
+            "{\"name\": \"demo\"}\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("src/demo/App.jsx")).unwrap(),
+            "<div style={{ color: 1 }} />"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join(".git/config.json")).unwrap(),
+            "{{ name }}"
+        );
+
+        // the content is left as it is without the `files` patterns
+        Manifest::default().render(&dir, &context).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("README.md")).unwrap(),
+            "# {{ name }}\n"
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_render_without_files() {
+        let dir = std::env::temp_dir().join(format!("yoo-render-all-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/{{ name }}")).unwrap();
+        fs::write(dir.join("package.json"), "{\"name\": \"{{ name }}\"}\n").unwrap();
+
+        // a manifest without the `files` key renders only the names
+        let manifest: Manifest = toml::from_str(
+            r#"
+            [[variables]]
+            name = "title"
+            "#,
+        )
+        .unwrap();
+        assert!(manifest.files.is_empty());
+
+        let mut context = RenderContext::default();
+        context.vars.insert("name".to_string(), "demo".to_string());
+        manifest.render(&dir, &context).unwrap();
+        assert!(dir.join("src/demo").is_dir());
+        assert_eq!(
+            fs::read_to_string(dir.join("package.json")).unwrap(),
+            "{\"name\": \"{{ name }}\"}\n"
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_lint() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            [[variables]]
+            name = "name"
+
+            [[variables]]
+            name = "page-title"
+
+            [[options]]
+            name = "api"
+            default = "graphql"
+
+            [[options.choices]]
+            value = "mock"
+
+            [[hooks]]
+            name = "Installing the dependencies"
+            run = "yarn install"
+            when = "api =="
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.lint().len(), 4);
+    }
+
+    #[test]
+    fn test_options() {
+        let dir = std::env::temp_dir().join(format!("yoo-options-{}", std::process::id()));
+        fs::create_dir_all(dir.join("mock")).unwrap();
+        fs::write(dir.join("mock/api.js"), "").unwrap();
+        fs::write(dir.join("tsconfig.json"), "").unwrap();
+        fs::write(dir.join("jsconfig.json"), "").unwrap();
+        fs::write(
+            dir.join("index.js"),
+            "{% if typescript %}ts{% else %}js{% endif %}",
+        )
+        .unwrap();
+
+        let manifest: Manifest = toml::from_str(
+            r#"
+            files = ["*.js"]
+
+            [[options]]
+            name = "typescript"
+            default = true
+            paths = ["tsconfig.json"]
+            off_paths = ["jsconfig.json"]
+
+            [[options]]
+            name = "api"
+            default = "real"
+
+            [[options.choices]]
+            value = "mock"
+            paths = ["mock"]
+
+            [[options.choices]]
+            value = "real"
+
+            [[hooks]]
+            name = "Installing the dependencies"
+            run = "yarn install"
+
+            [[hooks]]
+            name = "Generating the mock api"
+            run = "yarn mock"
+            when = "api == 'mock'"
+            "#,
+        )
+        .unwrap();
+
+        let mut context = RenderContext::default();
+        let given = BTreeMap::from([("typescript".to_string(), "no".to_string())]);
+        manifest.ask(&mut context, &given, false).unwrap();
+        assert_eq!(context.options["typescript"], OptionValue::Bool(false));
+        assert_eq!(
+            context.options["api"],
+            OptionValue::Choice("real".to_string())
+        );
+
+        let hooks = manifest.hooks(&context).unwrap();
+        assert_eq!(hooks.len(), 1);
+
+        manifest.remove_disabled_paths(&dir, &context).unwrap();
+        manifest.render(&dir, &context).unwrap();
+        assert!(!dir.join("mock").exists());
+        assert!(!dir.join("tsconfig.json").exists());
+        assert!(dir.join("jsconfig.json").exists());
+        assert_eq!(fs::read_to_string(dir.join("index.js")).unwrap(), "js");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_command() {
+        let dir = std::env::temp_dir();
+        assert!(run_command("echo out; echo err >&2", &dir).is_ok());
+
+        let err = run_command("echo first >&2; echo last >&2; exit 3", &dir)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("exit status: 3"));
+        assert!(err.ends_with("first\nlast"));
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{api::Project, manifest::RenderContext};
+
+pub(crate) const METADATA_FILE: &str = "yoo.toml";
+
+/// The metadata of a project, it lives in the root of the project repo
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Metadata {
+    pub project: Option<ProjectInfo>,
+    pub template: Option<TemplateProvenance>,
+    /// The settings of the project, see `yoo config explain`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<toml::Table>,
+}
+
+/// The project registered on the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ProjectInfo {
+    pub id: i32,
+    /// The address of the server the project is registered on
+    #[serde(default)]
+    pub server: String,
+    pub name: String,
+    pub description: String,
+    pub build_cmd: String,
+    pub dist: String,
+}
+
+impl ProjectInfo {
+    pub fn new(project: &Project, server: &str) -> ProjectInfo {
+        ProjectInfo {
+            id: project.id,
+            server: server.to_string(),
+            name: project.name.clone(),
+            description: project.description.clone(),
+            build_cmd: project.build_cmd.clone(),
+            dist: project.dist.clone(),
+        }
+    }
+}
+
+/// Where the project comes from, it is used to upgrade the project to a newer template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct TemplateProvenance {
+    pub name: String,
+    /// The git url or the local path of the template
+    pub repo: String,
+    #[serde(rename = "ref", default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subdir: Option<String>,
+    /// The commit of the template the project is rendered from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// The values the template is rendered with
+    #[serde(flatten)]
+    pub context: RenderContext,
+}
+
+impl Metadata {
+    /// find the metadata in the current directory or its parents, along with the project root
+    pub fn discover() -> Result<Option<(PathBuf, Metadata)>> {
+        let current_dir =
+            env::current_dir().with_context(|| "Failed to get the current directory")?;
+
+        for dir in current_dir.ancestors() {
+            if let Some(metadata) = Metadata::load(dir)? {
+                return Ok(Some((dir.to_path_buf(), metadata)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn load(dir: &Path) -> Result<Option<Metadata>> {
+        let metadata_file = dir.join(METADATA_FILE);
+        if !metadata_file.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&metadata_file)
+            .with_context(|| format!("Failed to read the {}", METADATA_FILE))?;
+        let metadata = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse the {}", METADATA_FILE))?;
+
+        Ok(Some(metadata))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let content = toml::to_string_pretty(self)
+            .with_context(|| format!("Failed to serialize the {}", METADATA_FILE))?;
+        fs::write(dir.join(METADATA_FILE), content)
+            .with_context(|| format!("Failed to write the {}", METADATA_FILE))
+    }
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{Metadata, TemplateProvenance};
+    use crate::manifest::{OptionValue, RenderContext};
+
+    #[test]
+    fn test_metadata_roundtrip() {
+        let mut context = RenderContext::new("demo", "a demo", "yarn build", "build");
+        context
+            .options
+            .insert("typescript".to_string(), OptionValue::Bool(true));
+
+        let metadata = Metadata {
+            project: None,
+            template: Some(TemplateProvenance {
+                name: "react".to_string(),
+                repo: "git@gitlab.com:yoo/templates.git".to_string(),
+                reference: Some("v1".to_string()),
+                subdir: None,
+                commit: Some("e1e1065".to_string()),
+                context,
+            }),
+            config: Some(toml::Table::from_iter([(
+                "server".to_string(),
+                toml::Value::String("https://yoo".to_string()),
+            )])),
+        };
+
+        let content = toml::to_string_pretty(&metadata).unwrap();
+        assert!(content.contains("ref = \"v1\""));
+
+        let metadata: Metadata = toml::from_str(&content).unwrap();
+        let template = metadata.template.unwrap();
+        assert_eq!(template.context.vars["name"], "demo");
+        assert_eq!(
+            template.context.options["typescript"],
+            OptionValue::Bool(true)
+        );
+        assert_eq!(
+            metadata.config.unwrap()["server"].as_str(),
+            Some("https://yoo")
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use git::GitError;
+
+use crate::api::ApiError;
+
+/// How the result of a command is printed, the logs always go to stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum OutputFormat {
+    /// Text and tables for humans
+    #[default]
+    Human,
+    /// A pretty printed json object
+    Json,
+    /// A json object on a single line
+    Ndjson,
+}
+
+impl OutputFormat {
+    pub fn is_human(self) -> bool {
+        self == OutputFormat::Human
+    }
+
+    /// the format with the `--json` flag of a command taken into account
+    pub fn or_json(self, json: bool) -> OutputFormat {
+        if json && self.is_human() {
+            OutputFormat::Json
+        } else {
+            self
+        }
+    }
+}
+
+/// print the result of a command, the human output is printed by the closure
+pub(crate) fn emit<T, F>(format: OutputFormat, value: &T, human: F) -> Result<()>
+where
+    T: Serialize + ?Sized,
+    F: FnOnce() -> Result<()>,
+{
+    match format {
+        OutputFormat::Human => human(),
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(value)?);
+            Ok(())
+        }
+        OutputFormat::Ndjson => {
+            println!("{}", serde_json::to_string(value)?);
+            Ok(())
+        }
+    }
+}
+
+/// The error printed in the json formats, the fields are kept stable for the scripts
+#[derive(Debug, Serialize)]
+pub(crate) struct ErrorOutput {
+    error: ErrorBody,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    /// One of `network`, `server`, `decode`, `session`, `git_auth`, `git_host_key`,
+    /// `git_rejected`, `canceled` and `error`
+    kind: &'static str,
+    message: String,
+    /// The http status of a server error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<u16>,
+    /// The code of a server error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<i32>,
+    /// The underlying errors, from the outermost to the root cause
+    causes: Vec<String>,
+}
+
+impl ErrorOutput {
+    pub fn new(err: &anyhow::Error) -> ErrorOutput {
+        let api_error = err.chain().find_map(|x| x.downcast_ref::<ApiError>());
+
+        let git_error = err.chain().find_map(|x| x.downcast_ref::<GitError>());
+
+        let kind = match (api_error, git_error) {
+            (Some(ApiError::Network(_)), _) => "network",
+            (Some(ApiError::Server { .. }), _) => "server",
+            (Some(ApiError::Decode(_)), _) => "decode",
+            (Some(ApiError::Session(_)), _) => "session",
+            (_, Some(GitError::Auth { .. })) => "git_auth",
+            (_, Some(GitError::HostKey { .. })) => "git_host_key",
+            (_, Some(GitError::Rejected { .. })) => "git_rejected",
+            _ if err.to_string() == "User canceled the operation" => "canceled",
+            _ => "error",
+        };
+        let code = match api_error {
+            Some(ApiError::Server { code, .. }) => *code,
+            _ => None,
+        };
+
+        ErrorOutput {
+            error: ErrorBody {
+                kind,
+                message: err.to_string(),
+                status: api_error.and_then(|x| x.status()).map(|x| x.as_u16()),
+                code,
+                causes: err.chain().skip(1).map(|x| x.to_string()).collect(),
+            },
+        }
+    }
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::ErrorOutput;
+    use crate::api::ApiError;
+    use git::GitError;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_error_output() {
+        let err = anyhow::Error::new(ApiError::Server {
+            status: StatusCode::BAD_REQUEST,
+            code: Some(40002),
+            msg: Some("project exists".to_string()),
+        })
+        .context("Failed to register the project");
+
+        let value = serde_json::to_value(ErrorOutput::new(&err)).unwrap();
+        assert_eq!(value["error"]["kind"], "server");
+        assert_eq!(value["error"]["message"], "Failed to register the project");
+        assert_eq!(value["error"]["status"], 400);
+        assert_eq!(value["error"]["code"], 40002);
+        assert_eq!(
+            value["error"]["causes"][0],
+            "project exists (status 400, code 40002)"
+        );
+
+        let err = anyhow::Error::msg("User canceled the operation");
+        let value = serde_json::to_value(ErrorOutput::new(&err)).unwrap();
+        assert_eq!(value["error"]["kind"], "canceled");
+        assert!(value["error"].get("status").is_none());
+
+        let err = anyhow::Error::new(GitError::Rejected {
+            reference: "refs/heads/dev".to_string(),
+            message: "not a fast-forward".to_string(),
+        });
+        let value = serde_json::to_value(ErrorOutput::new(&err)).unwrap();
+        assert_eq!(value["error"]["kind"], "git_rejected");
+    }
+}
@@ -0,0 +1,301 @@
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use console::{measure_text_width, pad_str, Alignment};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs};
+
+use crate::{
+    answers::ask_confirm, config::config_file, credentials, output::emit, terminal::is_interactive,
+    Cli,
+};
+
+// the name of the tokens when no profile is used
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Subcommand)]
+pub(crate) enum ProfileCommands {
+    /// List the profiles, the current one is marked with *
+    List,
+    /// Add a profile of a server and an account
+    Add {
+        name: String,
+        /// The yoo server address
+        #[arg(long)]
+        server: String,
+        /// The email to log in with
+        #[arg(long)]
+        email: Option<String>,
+        /// Use the profile by default
+        #[arg(long = "use")]
+        use_it: bool,
+    },
+    /// Use the profile by default
+    Use { name: String },
+    /// Remove a profile and its cached tokens
+    Remove {
+        name: String,
+        /// Remove it without confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+/// A server and the account to use on it
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct Profile {
+    pub server: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+/// The profiles in the config file of the user, the other settings of the file are kept as they are
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct Profiles {
+    /// The profile used when neither `--profile` nor YOO_PROFILE is given
+    #[serde(default, rename = "profile", skip_serializing_if = "Option::is_none")]
+    pub current: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    #[serde(flatten)]
+    pub rest: toml::Table,
+}
+
+impl Profiles {
+    pub fn load() -> Result<Profiles> {
+        let path = config_file()?;
+        if !path.exists() {
+            return Ok(Profiles::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read the {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Failed to parse the {}", path.display()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = config_file()?;
+        let content = toml::to_string_pretty(self)?;
+        fs::write(&path, content).with_context(|| format!("Failed to write the {}", path.display()))
+    }
+
+    /// the profile with its name, the missing one is an error
+    pub fn get(&self, name: &str) -> Result<&Profile> {
+        self.profiles.get(name).with_context(|| {
+            format!(
+                "The profile {} doesn't exist, run `yoo profile add` to add it",
+                name
+            )
+        })
+    }
+}
+
+/// A profile printed by the json formats
+#[derive(Serialize)]
+struct ProfileOutput<'a> {
+    name: &'a str,
+    server: &'a str,
+    email: Option<&'a str>,
+    current: bool,
+}
+
+impl<'a> ProfileOutput<'a> {
+    fn new(profiles: &'a Profiles, name: &'a str, profile: &'a Profile) -> ProfileOutput<'a> {
+        ProfileOutput {
+            name,
+            server: &profile.server,
+            email: profile.email.as_deref(),
+            current: profiles.current.as_deref() == Some(name),
+        }
+    }
+}
+
+/// the key of the cached tokens of a profile on a server
+pub(crate) fn session_key(profile: Option<&str>, server: &str) -> String {
+    format!(
+        "session:{}@{}",
+        profile.unwrap_or(DEFAULT_PROFILE),
+        server.trim_end_matches('/')
+    )
+}
+
+pub(crate) fn profile(cli: &Cli, command: ProfileCommands) -> Result<()> {
+    let mut profiles = Profiles::load()?;
+
+    match command {
+        ProfileCommands::List => {
+            let output: Vec<_> = profiles
+                .profiles
+                .iter()
+                .map(|(name, profile)| ProfileOutput::new(&profiles, name, profile))
+                .collect();
+            emit(cli.output, &output, || {
+                if profiles.profiles.is_empty() {
+                    println!("There are no profiles, run `yoo profile add` to add one");
+                } else {
+                    print_table(&profiles);
+                }
+                Ok(())
+            })
+        }
+        ProfileCommands::Add {
+            name,
+            server,
+            email,
+            use_it,
+        } => {
+            validate_profile_name(&name)?;
+            if profiles.profiles.contains_key(&name) {
+                return Err(anyhow::Error::msg(format!(
+                    "The profile {} already exists, remove it first",
+                    name
+                )));
+            }
+
+            let server = server.trim_end_matches('/').to_string();
+            profiles
+                .profiles
+                .insert(name.clone(), Profile { server, email });
+            if use_it || profiles.current.is_none() {
+                profiles.current = Some(name.clone());
+            }
+            profiles.save()?;
+
+            tracing::info!("Successfully added the profile {}", name);
+            let profile = profiles.get(&name)?;
+            emit(
+                cli.output,
+                &ProfileOutput::new(&profiles, &name, profile),
+                || Ok(()),
+            )
+        }
+        ProfileCommands::Use { name } => {
+            profiles.get(&name)?;
+            profiles.current = Some(name.clone());
+            profiles.save()?;
+
+            tracing::info!("Now the profile {} is used by default", name);
+            let profile = profiles.get(&name)?;
+            emit(
+                cli.output,
+                &ProfileOutput::new(&profiles, &name, profile),
+                || Ok(()),
+            )
+        }
+        ProfileCommands::Remove { name, yes } => {
+            let profile = profiles.get(&name)?.clone();
+            if !ask_confirm(
+                yes,
+                "yes",
+                &format!("Do you want to remove the profile {}?", name),
+                is_interactive(cli.output),
+            )? {
+                return Err(anyhow::Error::msg("User canceled the operation"));
+            }
+
+            credentials::store()?.delete(&session_key(Some(&name), &profile.server))?;
+            profiles.profiles.remove(&name);
+            if profiles.current.as_deref() == Some(name.as_str()) {
+                profiles.current = None;
+            }
+            profiles.save()?;
+
+            tracing::info!("Successfully removed the profile {}", name);
+            emit(
+                cli.output,
+                &serde_json::json!({ "name": name, "removed": true }),
+                || Ok(()),
+            )
+        }
+    }
+}
+
+fn validate_profile_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_')
+    {
+        return Err(anyhow::Error::msg(
+            "The profile name can only contain letters, digits, `-` and `_`",
+        ));
+    }
+    Ok(())
+}
+
+fn print_table(profiles: &Profiles) {
+    let rows: Vec<[&str; 4]> = profiles
+        .profiles
+        .iter()
+        .map(|(name, profile)| {
+            let current = if profiles.current.as_ref() == Some(name) {
+                "*"
+            } else {
+                ""
+            };
+            [
+                current,
+                name.as_str(),
+                profile.server.as_str(),
+                profile.email.as_deref().unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    let header = ["", "NAME", "SERVER", "EMAIL"];
+    let mut widths = header.map(measure_text_width);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(measure_text_width(cell));
+        }
+    }
+
+    for row in std::iter::once(header).chain(rows) {
+        println!(
+            "{} {}  {}  {}",
+            pad_str(row[0], widths[0].max(1), Alignment::Left, None),
+            pad_str(row[1], widths[1], Alignment::Left, None),
+            pad_str(row[2], widths[2], Alignment::Left, None),
+            row[3]
+        );
+    }
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{session_key, Profiles};
+
+    #[test]
+    fn test_profiles_keep_other_settings() {
+        let profiles: Profiles = toml::from_str(
+            r#"
+profile = "test"
+color = "never"
+
+[profiles.test]
+server = "https://test.yoo"
+email = "a@b.c"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(profiles.current.as_deref(), Some("test"));
+        assert_eq!(profiles.get("test").unwrap().server, "https://test.yoo");
+        assert!(profiles.get("prod").is_err());
+
+        let content = toml::to_string_pretty(&profiles).unwrap();
+        assert!(content.contains("color = \"never\""));
+    }
+
+    #[test]
+    fn test_session_key() {
+        assert_eq!(
+            session_key(None, "https://yoo/"),
+            "session:default@https://yoo"
+        );
+        assert_eq!(
+            session_key(Some("prod"), "https://yoo"),
+            "session:prod@https://yoo"
+        );
+    }
+}
@@ -0,0 +1,430 @@
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use console::{measure_text_width, pad_str, style, Alignment};
+use std::env;
+
+use crate::{
+    answers::ask_confirm,
+    api::{Project, ProjectPatch, YooApi},
+    loading::loading,
+    metadata::{Metadata, ProjectInfo, METADATA_FILE},
+    output::{emit, OutputFormat},
+    terminal::is_interactive,
+    Cli, REQUEST,
+};
+
+#[derive(Subcommand)]
+pub(crate) enum ProjectCommands {
+    /// List the projects on the server
+    List {
+        /// Only list the projects whose name contains the keyword
+        #[arg(long)]
+        search: Option<String>,
+        /// The page to list, starting from 1
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        page: u32,
+        /// The number of the projects on a page
+        #[arg(long, default_value_t = 20)]
+        size: u32,
+        /// Print the projects as json
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show a project, defaults to the project of the current repo
+    Show {
+        #[arg(value_name = "ID|NAME")]
+        project: Option<String>,
+        /// Print the project as json
+        #[arg(long)]
+        json: bool,
+    },
+    /// Update a project, defaults to the project of the current repo
+    Update {
+        #[arg(value_name = "ID|NAME")]
+        project: Option<String>,
+        /// The new description of the project
+        #[arg(long)]
+        description: Option<String>,
+        /// The new build command of the project
+        #[arg(long)]
+        build_cmd: Option<String>,
+        /// The new dist of the project
+        #[arg(long)]
+        dist: Option<String>,
+        /// Print the updated project as json
+        #[arg(long)]
+        json: bool,
+    },
+    /// Delete a project from the server
+    Delete {
+        #[arg(value_name = "ID|NAME")]
+        project: String,
+        /// Remove the GitLab repo of the project too, gitlab.server and YOO_GITLAB_TOKEN are required
+        #[arg(long)]
+        remove_repo: bool,
+        /// Delete it without confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Reconcile the yoo.toml of the project with the server, the server wins by default
+    Sync {
+        /// Update the server with the values of the yoo.toml instead
+        #[arg(long)]
+        push: bool,
+    },
+}
+
+pub(crate) fn project(cli: &Cli, command: ProjectCommands) -> Result<()> {
+    match command {
+        ProjectCommands::List {
+            search,
+            page,
+            size,
+            json,
+        } => {
+            let pb = loading("Fetching the projects")?;
+            let projects = YooApi::new(cli).list_projects(search.as_deref(), page, size)?;
+            pb.finish_and_clear();
+
+            emit(cli.output.or_json(json), &projects, || {
+                print_table(&projects.content);
+                if let Some(total) = projects.total {
+                    let pages = total.div_ceil(size as u64);
+                    println!(
+                        "\nPage {} of {}, {} projects in total",
+                        page,
+                        pages.max(1),
+                        total
+                    );
+                }
+                Ok(())
+            })
+        }
+        ProjectCommands::Show { project, json } => {
+            let project = resolve_project(cli, project.as_deref())?;
+            print_project(&project, cli.output.or_json(json))
+        }
+        ProjectCommands::Update {
+            project,
+            description,
+            build_cmd,
+            dist,
+            json,
+        } => {
+            let patch = ProjectPatch {
+                description: description.as_deref(),
+                build_cmd: build_cmd.as_deref(),
+                dist: dist.as_deref(),
+            };
+            let project = resolve_project(cli, project.as_deref())?;
+            let project = patch_project(cli, &project, &patch)?;
+            print_project(&project, cli.output.or_json(json))
+        }
+        ProjectCommands::Delete {
+            project,
+            remove_repo,
+            yes,
+        } => remove_project(cli, &project, remove_repo, yes),
+        ProjectCommands::Sync { push } => sync_project(cli, push),
+    }
+}
+
+fn print_project(project: &Project, output: OutputFormat) -> Result<()> {
+    emit(output, project, || {
+        println!("id:          {}", project.id);
+        println!("name:        {}", project.name);
+        println!("description: {}", project.description);
+        println!("build_cmd:   {}", project.build_cmd);
+        println!("dist:        {}", project.dist);
+        println!("ssh_url:     {}", project.ssh_url);
+        println!("http_url:    {}", project.http_url);
+        println!("web_url:     {}", project.web_url);
+        Ok(())
+    })
+}
+
+fn print_table(projects: &[Project]) {
+    let ids: Vec<String> = projects.iter().map(|x| x.id.to_string()).collect();
+    let rows: Vec<[&str; 4]> = projects
+        .iter()
+        .zip(&ids)
+        .map(|(x, id)| {
+            [
+                id.as_str(),
+                x.name.as_str(),
+                x.description.as_str(),
+                x.ssh_url.as_str(),
+            ]
+        })
+        .collect();
+
+    let header = ["ID", "NAME", "DESCRIPTION", "SSH_URL"];
+    let mut widths = header.map(measure_text_width);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(measure_text_width(cell));
+        }
+    }
+
+    for row in std::iter::once(header).chain(rows) {
+        println!(
+            "{}  {}  {}  {}",
+            pad_str(row[0], widths[0], Alignment::Left, None),
+            pad_str(row[1], widths[1], Alignment::Left, None),
+            pad_str(row[2], widths[2], Alignment::Left, None),
+            row[3]
+        );
+    }
+}
+
+// the project given by its id or name, or the one the current repo belongs to
+pub(crate) fn resolve_project(cli: &Cli, project: Option<&str>) -> Result<Project> {
+    let pb = loading("Fetching the project")?;
+    let result = match project {
+        Some(project) => match project.parse::<i32>() {
+            Ok(id) => Ok(YooApi::new(cli).get_project(id)?),
+            Err(_) => find_project(cli, project),
+        },
+        None => {
+            let id = Metadata::discover()?
+                .and_then(|(_, metadata)| metadata.project)
+                .map(|project| project.id)
+                .with_context(|| {
+                    format!(
+                        "There is no registered project in the {}, specify the id or the name of the project",
+                        METADATA_FILE
+                    )
+                })?;
+            Ok(YooApi::new(cli).get_project(id)?)
+        }
+    };
+    pb.finish_and_clear();
+    result
+}
+
+fn find_project(cli: &Cli, name: &str) -> Result<Project> {
+    YooApi::new(cli)
+        .list_projects(Some(name), 1, 100)?
+        .content
+        .into_iter()
+        .find(|x| x.name == name)
+        .with_context(|| format!("Failed to find the project {}", name))
+}
+
+// update the project on the server, and the yoo.toml if it is the project of the current repo
+fn patch_project(cli: &Cli, project: &Project, patch: &ProjectPatch) -> Result<Project> {
+    if patch.description.is_none() && patch.build_cmd.is_none() && patch.dist.is_none() {
+        return Err(anyhow::Error::msg(
+            "Nothing to update, set `--description`, `--build-cmd` or `--dist`",
+        ));
+    }
+
+    let pb = loading("Updating the project")?;
+    let project = YooApi::new(cli).update_project(project.id, patch)?;
+    pb.finish_and_clear();
+    tracing::info!("Successfully updated the project {}", project.name);
+
+    if let Some((root, mut metadata)) = Metadata::discover()? {
+        if metadata.project.as_ref().map(|x| x.id) == Some(project.id) {
+            metadata.project = Some(ProjectInfo::new(&project, cli.server.as_ref().unwrap()));
+            metadata.save(&root)?;
+            tracing::info!("Successfully updated the {}", METADATA_FILE);
+        }
+    }
+
+    Ok(project)
+}
+
+fn remove_project(cli: &Cli, project: &str, remove_repo: bool, yes: bool) -> Result<()> {
+    let project = resolve_project(cli, Some(project))?;
+    // the settings of GitLab are checked before anything is deleted,
+    // the repo can't be found again once the project is deleted
+    let gitlab_repo = if remove_repo {
+        Some(GitlabRepo::new(cli, &project)?)
+    } else {
+        None
+    };
+
+    let message = if remove_repo {
+        format!(
+            "Do you want to delete the project {} and its repo {}?",
+            project.name, project.web_url
+        )
+    } else {
+        format!("Do you want to delete the project {}?", project.name)
+    };
+    if !ask_confirm(yes, "yes", &message, is_interactive(cli.output))? {
+        return Err(anyhow::Error::msg("User canceled the operation"));
+    }
+
+    let pb = loading("Deleting the project")?;
+    YooApi::new(cli).delete_project(project.id)?;
+    pb.finish_and_clear();
+    tracing::info!("Successfully deleted the project {}", project.name);
+
+    if let Some(gitlab_repo) = gitlab_repo {
+        let pb = loading("Removing the repo")?;
+        gitlab_repo.delete()?;
+        pb.finish_and_clear();
+        tracing::info!("Successfully removed the repo {}", project.web_url);
+    }
+
+    if let Some((_, metadata)) = Metadata::discover()? {
+        if metadata.project.map(|x| x.id) == Some(project.id) {
+            tracing::warn!(
+                "The {} of the current repo still refers to the deleted project",
+                METADATA_FILE
+            );
+        }
+    }
+
+    let output = serde_json::json!({
+        "id": project.id,
+        "name": project.name,
+        "repo_removed": remove_repo,
+    });
+    emit(cli.output, &output, || Ok(()))
+}
+
+fn sync_project(cli: &Cli, push: bool) -> Result<()> {
+    let server = cli.server.as_ref().unwrap();
+    let (root, mut metadata) = Metadata::discover()?.with_context(|| {
+        format!(
+            "There is no {} in the current directory or its parents",
+            METADATA_FILE
+        )
+    })?;
+    let local = metadata.project.clone().with_context(|| {
+        "The project is not registered on the server, run `yoo init` to register it"
+    })?;
+
+    // an old metadata file has no server, it is assumed to be the current one
+    if !local.server.is_empty() && &local.server != server {
+        return Err(anyhow::Error::msg(format!(
+            "The project is registered on {}, but the server is {}",
+            local.server, server
+        )));
+    }
+
+    let pb = loading("Fetching the project")?;
+    let remote = YooApi::new(cli).get_project(local.id)?;
+    pb.finish_and_clear();
+
+    let differences: Vec<(&str, &str, &str)> = [
+        ("name", &local.name, &remote.name),
+        ("description", &local.description, &remote.description),
+        ("build_cmd", &local.build_cmd, &remote.build_cmd),
+        ("dist", &local.dist, &remote.dist),
+    ]
+    .into_iter()
+    .filter(|(_, local, remote)| local != remote)
+    .map(|(field, local, remote)| (field, local.as_str(), remote.as_str()))
+    .collect();
+
+    let changes: Vec<_> = differences
+        .iter()
+        .map(|(field, local, remote)| {
+            serde_json::json!({ "field": field, "local": local, "remote": remote })
+        })
+        .collect();
+    let direction = if push { "push" } else { "pull" };
+
+    if differences.is_empty() && !local.server.is_empty() {
+        tracing::info!("The {} is in sync with the server", METADATA_FILE);
+        let output = serde_json::json!({ "direction": direction, "changes": changes });
+        return emit(cli.output, &output, || Ok(()));
+    }
+
+    if cli.output.is_human() {
+        for (field, local, remote) in &differences {
+            println!(
+                "  {}: {} {} {}",
+                field,
+                style(local).red(),
+                if push { "->" } else { "<-" },
+                style(remote).green()
+            );
+        }
+    }
+
+    if push {
+        if local.name != remote.name {
+            tracing::warn!("The name of a project can not be changed, it is kept as it is");
+        }
+
+        let patch = ProjectPatch {
+            description: Some(&local.description),
+            build_cmd: Some(&local.build_cmd),
+            dist: Some(&local.dist),
+        };
+
+        let pb = loading("Updating the project")?;
+        let project = YooApi::new(cli).update_project(local.id, &patch)?;
+        pb.finish_and_clear();
+
+        metadata.project = Some(ProjectInfo::new(&project, server));
+        tracing::info!("Successfully updated the project on the server");
+    } else {
+        metadata.project = Some(ProjectInfo::new(&remote, server));
+        tracing::info!("Successfully updated the {} from the server", METADATA_FILE);
+    }
+
+    metadata.save(&root)?;
+    let output = serde_json::json!({ "direction": direction, "changes": changes });
+    emit(cli.output, &output, || Ok(()))
+}
+
+/// The repo of a project on GitLab
+struct GitlabRepo {
+    server: String,
+    token: String,
+    /// The path of the project on GitLab, e.g. `group/repo`
+    path: String,
+}
+
+impl GitlabRepo {
+    // the project path comes from the web url
+    fn new(cli: &Cli, project: &Project) -> Result<GitlabRepo> {
+        let server = cli.config.get("gitlab.server").with_context(|| {
+            "gitlab.server is not set, set YOO_GITLAB_SERVER or run `yoo config set gitlab.server`"
+        })?;
+        let token = env::var("YOO_GITLAB_TOKEN").with_context(|| "YOO_GITLAB_TOKEN is not set")?;
+
+        let path = project
+            .web_url
+            .split_once("://")
+            .and_then(|(_, rest)| rest.split_once('/'))
+            .map(|(_, path)| path.trim_end_matches('/').trim_end_matches(".git"))
+            .filter(|path| !path.is_empty())
+            .with_context(|| format!("Failed to get the repo path from {}", project.web_url))?;
+
+        Ok(GitlabRepo {
+            server: server.trim_end_matches('/').to_string(),
+            token,
+            path: path.to_string(),
+        })
+    }
+
+    // remove the repo through the GitLab api
+    fn delete(&self) -> Result<()> {
+        let resp = REQUEST
+            .delete(format!(
+                "{}/api/v4/projects/{}",
+                self.server,
+                self.path.replace('/', "%2F")
+            ))
+            .header("PRIVATE-TOKEN", &self.token)
+            .send()
+            .with_context(|| "Failed to remove the repo")?;
+
+        if !resp.status().is_success() {
+            return Err(anyhow::Error::msg(format!(
+                "Failed to remove the repo {}: {}",
+                self.path,
+                resp.status()
+            )));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use console::Term;
+use inquire::{Password, PasswordDisplayMode};
+use std::{env, path::PathBuf, sync::Arc};
+
+use crate::{expand_home, loading::suspend, Cli};
+
+// the keys ssh tries by default
+const DEFAULT_SSH_KEYS: &[&str] = &["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// how to talk to the git remotes, from the settings and the env
+pub(crate) fn remote_options(cli: &Cli) -> git::RemoteOptions {
+    let ssh_keys = match cli.config.get("git.ssh_key") {
+        Some(key) => vec![expand_home(key)],
+        None => dirs::home_dir()
+            .map(|home| {
+                DEFAULT_SSH_KEYS
+                    .iter()
+                    .map(|x| home.join(".ssh").join(x))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+
+    git::RemoteOptions {
+        ssh_keys,
+        token: env::var("YOO_GITLAB_TOKEN").ok(),
+        passphrase: Some(Arc::new(|key| {
+            // the passphrase is only asked in a terminal
+            if let Ok(passphrase) = env::var("YOO_SSH_PASSPHRASE") {
+                return Some(passphrase);
+            }
+            if !Term::stderr().is_term() {
+                return None;
+            }
+            // the prompt runs in the middle of a transfer, under its spinner
+            suspend(|| {
+                Password::new(&format!(
+                    "Please enter the passphrase of {}:",
+                    key.display()
+                ))
+                .without_confirmation()
+                .with_display_mode(PasswordDisplayMode::Masked)
+                .prompt()
+                .ok()
+            })
+        })),
+        known_hosts: dirs::home_dir()
+            .map(|home| home.join(".ssh").join("known_hosts"))
+            .into_iter()
+            .chain([PathBuf::from("/etc/ssh/ssh_known_hosts")])
+            .collect(),
+        fallback: cli
+            .config
+            .get("git.fallback")
+            .is_some_and(|x| matches!(x, "true" | "1" | "yes")),
+        progress: None,
+    }
+}
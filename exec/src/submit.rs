@@ -1,36 +1,194 @@
-use anyhow::Result;
-use inquire::Select;
+use anyhow::{Context, Result};
+use console::style;
+use git::{ChangeKind, WorkingTreeStatus};
+use inquire::{Select, Text};
+use std::fmt;
 
-use crate::{loading, Cli};
+use crate::{
+    loading::loading, metadata::Metadata, output::emit, remote::remote_options,
+    terminal::is_interactive, Cli,
+};
 
-pub(crate) fn submit(_: &Cli, branch: Option<String>) -> Result<()> {
-    // check if the current dir is a git repo
-    let repo = git::open_repo(
-        ".",
-    )?;
+/// What to do with the uncommitted changes before the push
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Uncommitted {
+    Stash,
+    Commit,
+    Abort,
+}
 
-    // check if there is uncommitted changes
-    if repo.has_uncommitted_changes()? {
-        tracing::error!("There are uncommitted changes, please commit them first");
-        return Ok(());
+impl fmt::Display for Uncommitted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Uncommitted::Stash => write!(f, "Stash them and submit"),
+            Uncommitted::Commit => write!(
+                f,
+                "Commit them and submit, the untracked files are left out"
+            ),
+            Uncommitted::Abort => write!(f, "Abort"),
+        }
     }
+}
 
+pub(crate) fn submit(cli: &Cli, branch: Option<String>) -> Result<()> {
+    // find the project which the repo belongs to
+    let mut registered = None;
+    let mut repo = match Metadata::discover()? {
+        Some((
+            root,
+            Metadata {
+                project: Some(project),
+                ..
+            },
+        )) => {
+            tracing::info!("Submitting the project {} ({})", project.name, project.id);
+            if cli.server.as_deref() != Some(project.server.as_str()) {
+                tracing::warn!(
+                    "The project is registered on {}, but the server is {}",
+                    project.server,
+                    cli.server.as_deref().unwrap_or_default()
+                );
+            }
+            registered = Some(project);
+            git::open_repo(
+                root.to_str()
+                    .with_context(|| "Failed to convert the path to string")?,
+            )?
+        }
+        _ => {
+            tracing::warn!(
+                "The repo is not registered on the server, run `yoo init` to register it"
+            );
+            // check if the current dir is a git repo
+            git::open_repo(".")?
+        }
+    };
+
+    let current = repo.current_branch()?;
+    let interactive = is_interactive(cli.output);
     let branch = match branch {
         Some(branch) => branch,
+        // the scripts submit the checked out branch instead of getting a prompt
+        None if !interactive => current.clone().with_context(|| {
+            "HEAD is detached, pass the branch to submit in non-interactive mode"
+        })?,
         None => {
             let branches = repo.list_branches()?;
-            Select::new("Select a branch to submit", branches).prompt()?
+            let cursor = branches
+                .iter()
+                .position(|x| Some(x) == current.as_ref())
+                .unwrap_or_default();
+            Select::new("Select a branch to submit", branches)
+                .with_starting_cursor(cursor)
+                .prompt()?
         }
     };
 
+    // the changes of the tracked files are not pushed, ask what to do with them
+    let status = repo.status()?;
+    if status.has_changes() {
+        if !status.conflicted.is_empty() {
+            return Err(anyhow::Error::msg(format!(
+                "There are conflicts in {}, please resolve them first",
+                status.conflicted.join(", ")
+            )));
+        }
+        // the scripts get an error instead of a prompt
+        if !interactive {
+            return Err(anyhow::Error::msg(
+                "There are uncommitted changes, please commit or stash them first",
+            ));
+        }
+
+        print_status(&status);
+        // a commit on HEAD only goes into the submitted branch when it is checked out
+        let options = if current.as_ref() == Some(&branch) {
+            vec![Uncommitted::Stash, Uncommitted::Commit, Uncommitted::Abort]
+        } else {
+            vec![Uncommitted::Stash, Uncommitted::Abort]
+        };
+        match Select::new("What to do with the uncommitted changes?", options).prompt()? {
+            Uncommitted::Stash => {
+                repo.stash("yoo submit")?;
+                tracing::info!("The changes are stashed, run `git stash pop` to restore them");
+            }
+            Uncommitted::Commit => {
+                let message = Text::new("Please enter the commit message:")
+                    .with_validator(inquire::required!("The commit message is required"))
+                    .prompt()?;
+                repo.commit_tracked(&message)?;
+            }
+            Uncommitted::Abort => return Ok(()),
+        }
+    }
+
     tracing::info!("Submitting the branch: {}", branch);
 
     // push the branch to the remote
     let pb = loading("Pushing")?;
-    repo.push(&branch)?;
+    repo.push(&branch, &remote_options(cli).with_progress(pb.transfer()))?;
     pb.finish_and_clear();
 
     tracing::info!("Successfully pushed the branch to the remote");
 
-    Ok(())
+    let output = serde_json::json!({
+        "project": registered,
+        "branch": branch,
+        "commit": repo.branch_commit(&branch)?,
+    });
+    emit(cli.output, &output, || Ok(()))
+}
+
+fn print_status(status: &WorkingTreeStatus) {
+    println!("There are uncommitted changes:");
+    let kind = |kind: ChangeKind| match kind {
+        ChangeKind::Added => "added",
+        ChangeKind::Deleted => "deleted",
+        ChangeKind::Modified => "modified",
+        ChangeKind::Renamed => "renamed",
+        ChangeKind::Other => "changed",
+    };
+    for change in &status.staged {
+        println!(
+            "  {} {:9} {}",
+            style("staged  ").green(),
+            kind(change.kind),
+            change.path
+        );
+    }
+    for rename in &status.renamed {
+        let label = if rename.staged {
+            "staged  "
+        } else {
+            "unstaged"
+        };
+        println!(
+            "  {} {:9} {} -> {}",
+            style(label).green(),
+            "renamed",
+            rename.from,
+            rename.to
+        );
+    }
+    for change in &status.modified {
+        println!(
+            "  {} {:9} {}",
+            style("unstaged").yellow(),
+            kind(change.kind),
+            change.path
+        );
+    }
+    if !status.untracked.is_empty() {
+        println!(
+            "  {} {} files",
+            style("untracked").dim(),
+            status.untracked.len()
+        );
+    }
+    if status.stashes > 0 {
+        println!(
+            "  {} changes are stashed already",
+            style(status.stashes).dim()
+        );
+    }
 }
@@ -0,0 +1,454 @@
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use console::{measure_text_width, pad_str, Alignment};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::{
+    answers::ask_confirm,
+    api::{ApiError, NewTemplate, YooApi},
+    cache::{self, TemplateIndex},
+    expand_home,
+    loading::loading,
+    manifest::{Manifest, RenderContext, MANIFEST_FILE},
+    output::emit,
+    terminal::is_interactive,
+    Cli,
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Template {
+    #[serde(default)]
+    pub id: Option<i32>,
+    pub name: String,
+    pub repo: String,
+    pub brief: String,
+}
+
+/// The template to create a project from, written as `<name|path|git-url>[#[ref][:subdir]]`
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct TemplateSpec {
+    pub location: String,
+    /// A branch, tag or commit of the template repo
+    pub reference: Option<String>,
+    /// The directory of the template in a repo with multiple templates
+    pub subdir: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum TemplateKind {
+    /// A template registered on the server
+    Name,
+    Path,
+    Git,
+}
+
+impl TemplateSpec {
+    pub fn parse(spec: &str) -> TemplateSpec {
+        let (location, fragment) = match spec.rsplit_once('#') {
+            Some((location, fragment)) => (location, fragment),
+            None => (spec, ""),
+        };
+        let (reference, subdir) = fragment.split_once(':').unwrap_or((fragment, ""));
+        let non_empty = |x: &str| (!x.is_empty()).then(|| x.to_string());
+
+        TemplateSpec {
+            location: location.to_string(),
+            reference: non_empty(reference),
+            subdir: non_empty(subdir.trim_matches('/')),
+        }
+    }
+
+    pub fn kind(&self) -> TemplateKind {
+        let location = self.location.as_str();
+        let scp_like = Regex::new(r"^[\w.-]+@[\w.-]+:").unwrap();
+
+        if location.contains("://") || location.ends_with(".git") || scp_like.is_match(location) {
+            TemplateKind::Git
+        } else if location.starts_with('.')
+            || location.starts_with('~')
+            || location.contains('/')
+            || location.contains('\\')
+            || Path::new(location).is_absolute()
+        {
+            TemplateKind::Path
+        } else {
+            TemplateKind::Name
+        }
+    }
+
+    /// the path of a local template, `~/` is expanded to the home dir
+    pub fn local_path(&self) -> PathBuf {
+        expand_home(&self.location)
+    }
+}
+
+#[derive(Subcommand)]
+pub(crate) enum TemplateCommands {
+    /// List the templates on the server
+    List {
+        /// Print the templates as json
+        #[arg(long)]
+        json: bool,
+        /// Only use the cached templates
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Show a template on the server
+    Show {
+        name: String,
+        /// Print the template as json
+        #[arg(long)]
+        json: bool,
+        /// Only use the cached templates
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Add a template to the server, only admins are allowed to
+    Add {
+        name: String,
+        /// The git url of the template repo
+        #[arg(long)]
+        repo: String,
+        /// A brief description of the template
+        #[arg(long)]
+        brief: String,
+    },
+    /// Remove a template from the server, only admins are allowed to
+    Remove {
+        name: String,
+        /// Remove it without confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Check the manifest of a local template and try to render it
+    Lint {
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+}
+
+pub(crate) fn template(cli: &Cli, command: TemplateCommands) -> Result<()> {
+    match command {
+        TemplateCommands::List { json, offline } => {
+            let templates = fetch_templates(cli, offline)?;
+            emit(cli.output.or_json(json), &templates, || {
+                print_table(&templates);
+                Ok(())
+            })
+        }
+        TemplateCommands::Show {
+            name,
+            json,
+            offline,
+        } => {
+            let template = find_template(cli, &name, offline)?;
+            emit(cli.output.or_json(json), &template, || {
+                println!("name:  {}", template.name);
+                println!("brief: {}", template.brief);
+                println!("repo:  {}", template.repo);
+                Ok(())
+            })
+        }
+        TemplateCommands::Add { name, repo, brief } => add_template(cli, &name, &repo, &brief),
+        TemplateCommands::Remove { name, yes } => remove_template(cli, &name, yes),
+        TemplateCommands::Lint { path } => lint(cli, &path),
+    }
+}
+
+/// fetch the templates from the server, the cached ones are used when they are not modified
+/// or the server is unreachable
+pub(crate) fn fetch_templates(cli: &Cli, offline: bool) -> Result<Vec<Template>> {
+    let cached = TemplateIndex::load()?;
+
+    if offline {
+        return cached.map(|index| index.templates).with_context(|| {
+            "There are no cached templates, run the command without --offline first"
+        });
+    }
+
+    let pb = loading("Fetching the templates")?;
+    let index = YooApi::new(cli).list_templates(cached.as_ref());
+    pb.finish_and_clear();
+
+    let index = match (index, cached) {
+        (Ok(None), Some(index)) => {
+            tracing::debug!("The cached templates are up to date");
+            return Ok(index.templates);
+        }
+        (Ok(Some(index)), _) => index,
+        (Ok(None), None) => unreachable!("the templates are always modified without a cache"),
+        (Err(err @ ApiError::Network(_)), Some(index)) => {
+            tracing::warn!(
+                "Failed to get the templates, using the cached ones: {}",
+                err
+            );
+            return Ok(index.templates);
+        }
+        (Err(err), _) => return Err(err).with_context(|| "Failed to get the templates"),
+    };
+    index.save()?;
+
+    Ok(index.templates)
+}
+
+fn find_template(cli: &Cli, name: &str, offline: bool) -> Result<Template> {
+    fetch_templates(cli, offline)?
+        .into_iter()
+        .find(|x| x.name == name)
+        .with_context(|| format!("Failed to find the template {}", name))
+}
+
+fn print_table(templates: &[Template]) {
+    let rows: Vec<[&str; 3]> = templates
+        .iter()
+        .map(|x| [x.name.as_str(), x.brief.as_str(), x.repo.as_str()])
+        .collect();
+
+    let mut widths = ["NAME", "BRIEF", "REPO"].map(measure_text_width);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(measure_text_width(cell));
+        }
+    }
+
+    for row in std::iter::once(["NAME", "BRIEF", "REPO"]).chain(rows) {
+        println!(
+            "{}  {}  {}",
+            pad_str(row[0], widths[0], Alignment::Left, None),
+            pad_str(row[1], widths[1], Alignment::Left, None),
+            row[2]
+        );
+    }
+}
+
+fn add_template(cli: &Cli, name: &str, repo: &str, brief: &str) -> Result<()> {
+    let payload = NewTemplate { name, repo, brief };
+
+    let pb = loading("Adding the template")?;
+    YooApi::new(cli)
+        .add_template(&payload)
+        .with_context(|| "Failed to add the template")?;
+    pb.finish_and_clear();
+
+    tracing::info!("Successfully added the template {}", name);
+
+    emit(cli.output, &payload, || Ok(()))
+}
+
+fn remove_template(cli: &Cli, name: &str, yes: bool) -> Result<()> {
+    let template = find_template(cli, name, false)?;
+    let id = template
+        .id
+        .with_context(|| "The server did not return the id of the template")?;
+
+    if !ask_confirm(
+        yes,
+        "yes",
+        &format!("Do you want to remove the template {}?", name),
+        is_interactive(cli.output),
+    )? {
+        return Err(anyhow::Error::msg("User canceled the operation"));
+    }
+
+    let pb = loading("Removing the template")?;
+    YooApi::new(cli)
+        .remove_template(id)
+        .with_context(|| "Failed to remove the template")?;
+    pb.finish_and_clear();
+
+    tracing::info!("Successfully removed the template {}", name);
+
+    let output = serde_json::json!({ "id": id, "name": name, "removed": true });
+    emit(cli.output, &output, || Ok(()))
+}
+
+// check the manifest, then render a copy of the template with the default values
+fn lint(cli: &Cli, path: &Path) -> Result<()> {
+    let manifest = Manifest::load(path)?
+        .with_context(|| format!("There is no {} in {}", MANIFEST_FILE, path.display()))?;
+
+    let problems = manifest.lint();
+    for problem in &problems {
+        tracing::error!("{}", problem);
+    }
+    if !problems.is_empty() {
+        return Err(anyhow::Error::msg(format!(
+            "Found {} problems in the {}",
+            problems.len(),
+            MANIFEST_FILE
+        )));
+    }
+
+    let dir = std::env::temp_dir().join(format!("yoo-lint-{}", std::process::id()));
+    copy_template(path, &dir)?;
+
+    let mut context = RenderContext::new(
+        "yoo-lint",
+        "A project to lint the template",
+        "yarn build",
+        "build",
+    );
+    // the variables without a default get a placeholder
+    let given = manifest
+        .variables
+        .iter()
+        .filter(|x| x.default.is_none())
+        .map(|x| (x.name.clone(), format!("<{}>", x.name)))
+        .collect();
+
+    let result = manifest
+        .ask(&mut context, &given, false)
+        .and_then(|_| manifest.hooks(&context))
+        .and_then(|_| manifest.apply(&dir, &context));
+
+    fs::remove_dir_all(&dir).with_context(|| "Failed to remove the rendered template")?;
+    result?;
+
+    tracing::info!("The template {} looks good", path.display());
+
+    let output = serde_json::json!({ "path": path, "ok": true });
+    emit(cli.output, &output, || Ok(()))
+}
+
+/// put the template into the project dir as a git repo on the master branch, a git template
+/// keeps its history while a local directory or a subdirectory is copied into a new repo,
+/// the commit of the template is returned when it is known
+pub(crate) fn checkout_template(
+    repo: &str,
+    reference: Option<&str>,
+    subdir: Option<&str>,
+    project_path: &str,
+    offline: bool,
+    options: &git::RemoteOptions,
+) -> Result<Option<String>> {
+    let local = Path::new(repo).is_dir() && reference.is_none();
+
+    // remote templates are cloned from their cached mirror
+    let mirror;
+    let repo = if Path::new(repo).is_dir() {
+        repo
+    } else {
+        mirror = cache::mirror(repo, offline, options)?;
+        mirror
+            .to_str()
+            .with_context(|| "Failed to convert the path to string")?
+    };
+
+    if !local && subdir.is_none() {
+        let git_repo = git::clone(repo, project_path, options)?;
+        if let Some(reference) = reference {
+            git_repo.checkout_branch_at("master", reference)?;
+        }
+        return git_repo.head_commit().map(Some);
+    }
+
+    // the working tree of a local template may not be committed, its HEAD is the best guess
+    let mut commit = if local {
+        git::open_repo(repo)
+            .and_then(|git_repo| git_repo.head_commit())
+            .ok()
+    } else {
+        None
+    };
+
+    let cloned = if local {
+        None
+    } else {
+        let dir = std::env::temp_dir().join(format!("yoo-template-{}", std::process::id()));
+        let dir_path = dir
+            .to_str()
+            .with_context(|| "Failed to convert the path to string")?;
+        let git_repo = git::clone(repo, dir_path, options)?;
+        if let Some(reference) = reference {
+            git_repo.checkout_branch_at("master", reference)?;
+        }
+        commit = Some(git_repo.head_commit()?);
+        Some(dir)
+    };
+
+    let mut source = cloned.clone().unwrap_or_else(|| PathBuf::from(repo));
+    if let Some(subdir) = subdir {
+        source = source.join(subdir);
+    }
+
+    let result = if source.is_dir() {
+        copy_template(&source, Path::new(project_path))
+    } else {
+        Err(anyhow::Error::msg(format!(
+            "There is no directory {} in the template",
+            subdir.unwrap_or_default()
+        )))
+    };
+
+    if let Some(dir) = cloned {
+        fs::remove_dir_all(dir).with_context(|| "Failed to remove the cloned template")?;
+    }
+    result?;
+
+    git::init(project_path)?;
+
+    Ok(commit)
+}
+
+fn copy_template(from: &Path, to: &Path) -> Result<()> {
+    for entry in WalkDir::new(from)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+    {
+        let entry = entry.with_context(|| "Failed to walk through the template")?;
+        let target = to.join(
+            entry
+                .path()
+                .strip_prefix(from)
+                .with_context(|| "Failed to get the relative path")?,
+        );
+
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)
+                .with_context(|| format!("Failed to create {}", target.display()))?;
+        } else {
+            fs::copy(entry.path(), &target)
+                .with_context(|| format!("Failed to copy {}", entry.path().display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{TemplateKind, TemplateSpec};
+
+    #[test]
+    fn test_parse_spec() {
+        let spec = TemplateSpec::parse("react#v2");
+        assert_eq!(spec.location, "react");
+        assert_eq!(spec.reference.as_deref(), Some("v2"));
+        assert_eq!(spec.kind(), TemplateKind::Name);
+
+        let spec = TemplateSpec::parse("git@gitlab.com:yoo/templates.git#dev:react/");
+        assert_eq!(spec.location, "git@gitlab.com:yoo/templates.git");
+        assert_eq!(spec.reference.as_deref(), Some("dev"));
+        assert_eq!(spec.subdir.as_deref(), Some("react"));
+        assert_eq!(spec.kind(), TemplateKind::Git);
+
+        let spec = TemplateSpec::parse("../templates#:vue");
+        assert_eq!(spec.reference, None);
+        assert_eq!(spec.subdir.as_deref(), Some("vue"));
+        assert_eq!(spec.kind(), TemplateKind::Path);
+
+        let spec = TemplateSpec::parse("~/templates/react#v1");
+        assert_eq!(spec.kind(), TemplateKind::Path);
+        assert_eq!(
+            spec.local_path(),
+            dirs::home_dir().unwrap().join("templates/react")
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use clap::ValueEnum;
+use console::Term;
+use std::{
+    env,
+    io::{stdin, IsTerminal},
+};
+
+use crate::{
+    loading::{set_mode, ProgressMode},
+    output::OutputFormat,
+};
+
+/// When to color the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ColorChoice {
+    /// Color a terminal unless NO_COLOR is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// whether to color a stream, NO_COLOR only matters when the colors are not forced
+    pub fn enabled(self, is_term: bool, no_color: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => is_term && !no_color,
+        }
+    }
+}
+
+/// set up the colors of stdout and stderr and how the progress is shown,
+/// whether stderr is colored is returned for the logs
+pub(crate) fn setup(color: ColorChoice, quiet: bool) -> bool {
+    // https://no-color.org, an empty NO_COLOR is ignored
+    let no_color = env::var_os("NO_COLOR").is_some_and(|x| !x.is_empty());
+    let stderr_is_term = Term::stderr().is_term();
+
+    let stdout = color.enabled(Term::stdout().is_term(), no_color);
+    let stderr = color.enabled(stderr_is_term, no_color);
+    console::set_colors_enabled(stdout);
+    console::set_colors_enabled_stderr(stderr);
+
+    set_mode(if quiet {
+        ProgressMode::Hidden
+    } else if stderr_is_term {
+        ProgressMode::Spinner
+    } else {
+        ProgressMode::Plain
+    });
+
+    stderr
+}
+
+/// whether the user can be prompted, the json formats and the pipes are never prompted
+pub(crate) fn is_interactive(output: OutputFormat) -> bool {
+    output.is_human() && stdin().is_terminal() && Term::stderr().is_term()
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::ColorChoice;
+
+    #[test]
+    fn test_color_choice() {
+        assert!(ColorChoice::Auto.enabled(true, false));
+        assert!(!ColorChoice::Auto.enabled(true, true));
+        assert!(!ColorChoice::Auto.enabled(false, false));
+        assert!(ColorChoice::Always.enabled(false, true));
+        assert!(!ColorChoice::Never.enabled(true, false));
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use console::style;
+use serde::Serialize;
+use std::{fs, path::Path};
+
+use crate::{
+    loading::loading,
+    manifest::Manifest,
+    metadata::{Metadata, TemplateProvenance, METADATA_FILE},
+    output::emit,
+    remote::remote_options,
+    template::checkout_template,
+    Cli,
+};
+
+#[derive(Args)]
+pub(crate) struct UpgradeArgs {
+    /// The branch, tag or commit of the template to upgrade to,
+    /// defaults to the one the project is created from or the default branch
+    #[arg(long, value_name = "REF")]
+    to: Option<String>,
+
+    /// Only use the cached templates, never fetch them
+    #[arg(long)]
+    offline: bool,
+}
+
+/// The result of `yoo upgrade` printed by the json formats
+#[derive(Serialize)]
+struct UpgradeOutput<'a> {
+    template: &'a str,
+    from: &'a str,
+    to: &'a str,
+    up_to_date: bool,
+    changed: &'a [String],
+    conflicts: &'a [String],
+    /// Whether the merge is committed, it isn't when there are conflicts
+    committed: bool,
+}
+
+// merge the changes of the template since the project is created into the project
+pub(crate) fn upgrade(cli: &Cli, args: UpgradeArgs) -> Result<()> {
+    let (root, mut metadata) = Metadata::discover()?.with_context(|| {
+        format!(
+            "There is no {} in the current directory or its parents",
+            METADATA_FILE
+        )
+    })?;
+    let git_repo = git::open_repo(
+        root.to_str()
+            .with_context(|| "Failed to convert the path to string")?,
+    )?;
+    let template = metadata
+        .template
+        .clone()
+        .with_context(|| "The project is not created from a template")?;
+    let base_commit = template.commit.clone().with_context(|| {
+        "The commit of the template is unknown, the project can not be upgraded"
+    })?;
+
+    // the merge overwrites the files of the template, the untracked ones included
+    if !git_repo.status()?.is_clean() {
+        return Err(anyhow::Error::msg(
+            "There are uncommitted changes or untracked files, please commit, stash or remove them first",
+        ));
+    }
+
+    let target = args
+        .to
+        .or_else(|| template.reference.clone())
+        .unwrap_or_else(|| "HEAD".to_string());
+
+    // render both versions of the template with the values the project is created with
+    let dir = std::env::temp_dir().join(format!("yoo-upgrade-{}", std::process::id()));
+    let pb = loading("Fetching the template")?;
+    let options = remote_options(cli).with_progress(pb.transfer());
+    let render = |reference: &str, name: &str| {
+        render_template(
+            &template,
+            reference,
+            &dir.join(name),
+            args.offline,
+            &options,
+        )
+    };
+    let result = render(&base_commit, "base")
+        .and_then(|base| render(&target, "target").map(|target| (base, target)));
+    pb.finish_and_clear();
+
+    let trees = result.and_then(|((_, base_dir), (target_commit, target_dir))| {
+        if target_commit == base_commit {
+            return Ok(None);
+        }
+        let base_tree = git_repo.write_tree_from_dir(&base_dir)?;
+        let target_tree = git_repo.write_tree_from_dir(&target_dir)?;
+        Ok(Some((target_commit, base_tree, target_tree)))
+    });
+
+    if dir.exists() {
+        fs::remove_dir_all(&dir).with_context(|| "Failed to remove the rendered template")?;
+    }
+
+    let (target_commit, base_tree, target_tree) = match trees? {
+        Some(trees) => trees,
+        None => {
+            tracing::info!("The project is already up to date with the template");
+            let output = UpgradeOutput {
+                template: &template.name,
+                from: &base_commit,
+                to: &base_commit,
+                up_to_date: true,
+                changed: &[],
+                conflicts: &[],
+                committed: false,
+            };
+            return emit(cli.output, &output, || Ok(()));
+        }
+    };
+
+    let summary = git_repo.merge_trees_into_head(&base_tree, &target_tree)?;
+
+    if let Some(template) = metadata.template.as_mut() {
+        template.commit = Some(target_commit.clone());
+    }
+    metadata.save(&root)?;
+
+    let committed = summary.conflicts.is_empty();
+    if committed {
+        // only the merged files and the metadata, nothing else is swept into the commit
+        let mut paths = summary.changed.clone();
+        paths.push(METADATA_FILE.to_string());
+        git_repo.commit_paths(
+            &paths,
+            &format!(
+                "chore: upgrade the template {} to {}",
+                template.name,
+                short(&target_commit)
+            ),
+        )?;
+        tracing::info!("Successfully upgraded the project");
+    } else {
+        tracing::warn!(
+            "{} files are left with conflicts, resolve them and commit the changes",
+            summary.conflicts.len()
+        );
+    }
+
+    let output = UpgradeOutput {
+        template: &template.name,
+        from: &base_commit,
+        to: &target_commit,
+        up_to_date: false,
+        changed: &summary.changed,
+        conflicts: &summary.conflicts,
+        committed,
+    };
+    emit(cli.output, &output, || {
+        println!(
+            "Upgraded the template {} from {} to {}",
+            template.name,
+            short(&base_commit),
+            short(&target_commit)
+        );
+        for path in &summary.changed {
+            if summary.conflicts.contains(path) {
+                println!("  {} {}", style("conflict").red(), path);
+            } else {
+                println!("  {}  {}", style("updated").green(), path);
+            }
+        }
+        Ok(())
+    })
+}
+
+// check out the template at the reference into the directory and render it,
+// the commit and the rendered directory are returned
+fn render_template(
+    template: &TemplateProvenance,
+    reference: &str,
+    dir: &Path,
+    offline: bool,
+    options: &git::RemoteOptions,
+) -> Result<(String, std::path::PathBuf)> {
+    fs::create_dir_all(dir).with_context(|| "Failed to create the template directory")?;
+    let path = dir
+        .to_str()
+        .with_context(|| "Failed to convert the path to string")?;
+
+    let commit = checkout_template(
+        &template.repo,
+        Some(reference),
+        template.subdir.as_deref(),
+        path,
+        offline,
+        options,
+    )?
+    .with_context(|| format!("Failed to find the commit of the template at {}", reference))?;
+
+    if let Some(manifest) = Manifest::load(dir)? {
+        manifest.apply(dir, &template.context)?;
+    }
+
+    Ok((commit, dir.to_path_buf()))
+}
+
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(7)]
+}
@@ -3,10 +3,12 @@ use anyhow::{Context, Error, Result};
 
 pub fn exec_git_command(args: &Vec<&str>, working_dir: Option<&str>) -> Result<String> {
     let output = Command::new("git")
-        .current_dir(if let Some(working_dir) = working_dir { working_dir } else { "." })
+        .current_dir(working_dir.unwrap_or("."))
         .args(args)
         .output()
-        .with_context(|| "Failed to execute the git command")?;
+        .with_context(|| {
+            "Failed to execute the git command, make sure git is installed and in the PATH"
+        })?;
 
     if output.status.success() {
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
@@ -0,0 +1,378 @@
+use anyhow::{Context, Result};
+use git2::{Delta, DiffFindOptions, DiffOptions, ErrorCode, Oid, Sort};
+
+use crate::GitRepo;
+
+/// A commit listed by `GitRepo::log`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitInfo {
+    pub id: String,
+    /// The first line of the message
+    pub summary: String,
+    pub message: String,
+    pub author: String,
+    pub email: String,
+    /// The unix time of the commit
+    pub time: i64,
+}
+
+/// Which commits `GitRepo::log` lists, the newest first
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// The branch, tag or commit to start from, defaults to HEAD
+    pub from: Option<String>,
+    /// Hide the commits reachable from it, e.g. `origin/dev` for the unpushed commits
+    pub exclude: Option<String>,
+    /// Only the commits whose author name or email contains it
+    pub author: Option<String>,
+    /// Only the commits whose message contains it
+    pub grep: Option<String>,
+    /// Only the commits which change the file or the directory
+    pub path: Option<String>,
+    /// Only the commits since the unix time
+    pub since: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+    /// A copied file, a changed file type and so on
+    Other,
+}
+
+/// The change of a file between two refs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStat {
+    pub path: String,
+    /// The path before it is renamed
+    pub old_path: Option<String>,
+    pub kind: ChangeKind,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+/// The changes between two refs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffStats {
+    pub files: Vec<FileStat>,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+/// How far a branch is from its upstream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AheadBehind {
+    /// The upstream, e.g. `origin/dev`
+    pub upstream: String,
+    /// The commits which are not pushed
+    pub ahead: usize,
+    /// The commits which are not pulled
+    pub behind: usize,
+}
+
+impl GitRepo {
+    /// the branch HEAD points at, `None` when HEAD is detached
+    pub fn current_branch(&self) -> Result<Option<String>> {
+        let head = match self.repo.head() {
+            Ok(head) => head,
+            // there is no commit yet, the branch is only a name in HEAD
+            Err(err) if err.code() == ErrorCode::UnbornBranch => {
+                let head = self
+                    .repo
+                    .find_reference("HEAD")
+                    .with_context(|| "Failed to get the head")?;
+                return Ok(head
+                    .symbolic_target()
+                    .and_then(|x| x.strip_prefix("refs/heads/"))
+                    .map(|x| x.to_string()));
+            }
+            Err(err) => return Err(err).with_context(|| "Failed to get the head"),
+        };
+
+        if !head.is_branch() {
+            return Ok(None);
+        }
+        Ok(head.shorthand().map(|x| x.to_string()))
+    }
+
+    /// the commits the filter selects, the newest first
+    pub fn log(&self, filter: &LogFilter) -> Result<Vec<CommitInfo>> {
+        let mut walk = self
+            .repo
+            .revwalk()
+            .with_context(|| "Failed to walk the commits")?;
+        walk.set_sorting(Sort::TIME)?;
+        walk.push(self.resolve(filter.from.as_deref().unwrap_or("HEAD"))?)?;
+        if let Some(exclude) = &filter.exclude {
+            walk.hide(self.resolve(exclude)?)?;
+        }
+
+        let mut commits = vec![];
+        for id in walk {
+            if filter.limit.is_some_and(|limit| commits.len() >= limit) {
+                break;
+            }
+
+            let commit = self
+                .repo
+                .find_commit(id.with_context(|| "Failed to walk the commits")?)
+                .with_context(|| "Failed to find the commit")?;
+            let time = commit.time().seconds();
+            if filter.since.is_some_and(|since| time < since) {
+                // the commits are sorted by time, the rest are older
+                break;
+            }
+
+            let author = commit.author();
+            let info = CommitInfo {
+                id: commit.id().to_string(),
+                summary: commit.summary().unwrap_or_default().to_string(),
+                message: commit.message().unwrap_or_default().to_string(),
+                author: author.name().unwrap_or_default().to_string(),
+                email: author.email().unwrap_or_default().to_string(),
+                time,
+            };
+
+            if let Some(pattern) = &filter.author {
+                if !info.author.contains(pattern.as_str()) && !info.email.contains(pattern.as_str())
+                {
+                    continue;
+                }
+            }
+            if let Some(pattern) = &filter.grep {
+                if !info.message.contains(pattern.as_str()) {
+                    continue;
+                }
+            }
+            if let Some(path) = &filter.path {
+                let parent = commit.parents().next();
+                let stats = self.diff_trees(
+                    parent.map(|x| x.tree()).transpose()?.as_ref(),
+                    &commit.tree()?,
+                    Some(path),
+                )?;
+                if stats.files.is_empty() {
+                    continue;
+                }
+            }
+
+            commits.push(info);
+        }
+
+        Ok(commits)
+    }
+
+    /// the files changed from a ref to another one, the renames are detected
+    pub fn diff_stats(&self, from: &str, to: &str) -> Result<DiffStats> {
+        let from = self.repo.find_commit(self.resolve(from)?)?.tree()?;
+        let to = self.repo.find_commit(self.resolve(to)?)?.tree()?;
+        self.diff_trees(Some(&from), &to, None)
+    }
+
+    /// how far the branch is from its upstream, defaults to the current branch,
+    /// `None` when it doesn't track a branch
+    pub fn ahead_behind(&self, branch: Option<&str>) -> Result<Option<AheadBehind>> {
+        let name = match branch {
+            Some(branch) => branch.to_string(),
+            None => match self.current_branch()? {
+                Some(branch) => branch,
+                None => return Ok(None),
+            },
+        };
+        let branch = self
+            .repo
+            .find_branch(&name, git2::BranchType::Local)
+            .with_context(|| format!("Failed to find the branch {}", name))?;
+        let upstream = match branch.upstream() {
+            Ok(upstream) => upstream,
+            Err(err) if err.code() == ErrorCode::NotFound => return Ok(None),
+            Err(err) => return Err(err).with_context(|| "Failed to get the upstream"),
+        };
+
+        let local = branch
+            .get()
+            .target()
+            .with_context(|| "The branch has no commit")?;
+        let remote = upstream
+            .get()
+            .target()
+            .with_context(|| "The upstream has no commit")?;
+        let (ahead, behind) = self
+            .repo
+            .graph_ahead_behind(local, remote)
+            .with_context(|| "Failed to compare the branch with its upstream")?;
+
+        Ok(Some(AheadBehind {
+            upstream: upstream.name()?.unwrap_or_default().to_string(),
+            ahead,
+            behind,
+        }))
+    }
+
+    /// tag the ref, defaults to HEAD, the tag is annotated when there is a message
+    pub fn create_tag(
+        &self,
+        name: &str,
+        target: Option<&str>,
+        message: Option<&str>,
+    ) -> Result<()> {
+        let object = self
+            .repo
+            .find_object(self.resolve(target.unwrap_or("HEAD"))?, None)
+            .with_context(|| "Failed to find the target of the tag")?;
+
+        match message {
+            Some(message) => {
+                let signature = self.repo.signature().with_context(|| {
+                    "Failed to get the signature, please config the user.name and user.email of git"
+                })?;
+                self.repo.tag(name, &object, &signature, message, false)
+            }
+            None => self.repo.tag_lightweight(name, &object, false),
+        }
+        .with_context(|| format!("Failed to create the tag {}", name))?;
+
+        Ok(())
+    }
+
+    // the commit of a branch, a tag or a commit id
+    fn resolve(&self, reference: &str) -> Result<Oid> {
+        let commit = self
+            .repo
+            .revparse_single(reference)
+            .and_then(|x| x.peel_to_commit())
+            .with_context(|| format!("Failed to find the reference {}", reference))?;
+        Ok(commit.id())
+    }
+
+    fn diff_trees(
+        &self,
+        from: Option<&git2::Tree>,
+        to: &git2::Tree,
+        path: Option<&str>,
+    ) -> Result<DiffStats> {
+        let mut options = DiffOptions::new();
+        if let Some(path) = path {
+            options.pathspec(path);
+        }
+        let mut diff = self
+            .repo
+            .diff_tree_to_tree(from, Some(to), Some(&mut options))
+            .with_context(|| "Failed to diff the trees")?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+            .with_context(|| "Failed to find the renames")?;
+
+        let mut stats = DiffStats::default();
+        for (i, delta) in diff.deltas().enumerate() {
+            let (_, insertions, deletions) = match git2::Patch::from_diff(&diff, i)? {
+                Some(patch) => patch.line_stats()?,
+                // a binary file
+                None => (0, 0, 0),
+            };
+            let path = |file: git2::DiffFile| {
+                file.path()
+                    .map(|x| x.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            };
+            let kind = match delta.status() {
+                Delta::Added => ChangeKind::Added,
+                Delta::Deleted => ChangeKind::Deleted,
+                Delta::Modified => ChangeKind::Modified,
+                Delta::Renamed => ChangeKind::Renamed,
+                _ => ChangeKind::Other,
+            };
+
+            stats.insertions += insertions;
+            stats.deletions += deletions;
+            stats.files.push(FileStat {
+                path: path(delta.new_file()),
+                old_path: Some(path(delta.old_file())).filter(|_| kind == ChangeKind::Renamed),
+                kind,
+                insertions,
+                deletions,
+            });
+        }
+
+        Ok(stats)
+    }
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{ChangeKind, LogFilter};
+    use crate::{PullOutcome, RemoteOptions};
+    use git2::{Repository, RepositoryInitOptions};
+    use std::fs;
+
+    #[test]
+    fn test_history() {
+        let dir = std::env::temp_dir().join(format!("yoo-git-history-{}", std::process::id()));
+        let remote = dir.join("remote.git");
+        Repository::init_opts(
+            &remote,
+            RepositoryInitOptions::new()
+                .bare(true)
+                .initial_head("master"),
+        )
+        .unwrap();
+
+        let options = RemoteOptions::default();
+        let clone = |name: &str| {
+            let path = dir.join(name);
+            let repo =
+                crate::clone(remote.to_str().unwrap(), path.to_str().unwrap(), &options).unwrap();
+            let mut config = repo.repo.config().unwrap();
+            config.set_str("user.name", "yoo").unwrap();
+            config.set_str("user.email", "yoo@yoo").unwrap();
+            (path, repo)
+        };
+
+        let (first_dir, first) = clone("first");
+        fs::write(first_dir.join("a"), "a\n").unwrap();
+        first.commit_all("add a").unwrap();
+        first.push("master", &options).unwrap();
+        let (_, second) = clone("second");
+        assert_eq!(second.current_branch().unwrap().as_deref(), Some("master"));
+
+        fs::rename(first_dir.join("a"), first_dir.join("b")).unwrap();
+        fs::write(first_dir.join("c"), "c\nc\n").unwrap();
+        first.commit_all("move a to b").unwrap();
+        first.create_tag("v1", None, Some("v1")).unwrap();
+        first.push("master", &options).unwrap();
+        first.push_tag("v1", &options).unwrap();
+
+        let log = first
+            .log(&LogFilter {
+                path: Some("c".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].summary, "move a to b");
+
+        let stats = first.diff_stats("HEAD~1", "v1").unwrap();
+        assert_eq!((stats.insertions, stats.deletions), (2, 0));
+        assert!(stats
+            .files
+            .iter()
+            .any(|x| x.kind == ChangeKind::Renamed && x.old_path.as_deref() == Some("a")));
+
+        let fetched = second.fetch("origin", &options).unwrap();
+        assert!(fetched.updated.iter().any(|x| x.name == "refs/tags/v1"));
+        let ahead_behind = second.ahead_behind(None).unwrap().unwrap();
+        assert_eq!((ahead_behind.ahead, ahead_behind.behind), (0, 1));
+        assert!(matches!(
+            second.pull(&options).unwrap(),
+            PullOutcome::FastForward { .. }
+        ));
+        assert_eq!(second.pull(&options).unwrap(), PullOutcome::UpToDate);
+        assert_eq!(second.head_commit().unwrap(), first.head_commit().unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use git2::{ErrorClass, ErrorCode};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::{fs, path::PathBuf};
+
+/// check the host key of an ssh remote against the known_hosts files like ssh does,
+/// an unknown, a changed or a revoked key is refused
+pub(crate) fn check(
+    files: &[PathBuf],
+    host: &str,
+    port: u16,
+    key_type: &str,
+    key: &[u8],
+) -> Result<(), git2::Error> {
+    // ssh writes the hosts on another port than 22 as `[host]:port`
+    let name = if port == 22 {
+        host.to_lowercase()
+    } else {
+        format!("[{}]:{}", host.to_lowercase(), port)
+    };
+
+    let mut known = false;
+    let mut changed = false;
+    for file in files {
+        let content = fs::read_to_string(file).unwrap_or_default();
+        for line in content.lines() {
+            let mut fields = line.split_whitespace().peekable();
+            let marker = fields.next_if(|x| x.starts_with('@'));
+            let (Some(hosts), Some(entry_type), Some(entry_key)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            if hosts.starts_with('#') || !matches_hosts(hosts, &name) {
+                continue;
+            }
+            let Ok(entry_key) = STANDARD.decode(entry_key) else {
+                continue;
+            };
+
+            match marker {
+                Some("@revoked") if entry_key == key => {
+                    return Err(refuse(format!("the host key of {} is revoked", name)));
+                }
+                // the certificates of the hosts are not supported
+                Some(_) => {}
+                None if entry_type == key_type && entry_key == key => known = true,
+                None if entry_type == key_type => changed = true,
+                None => {}
+            }
+        }
+    }
+
+    if known {
+        Ok(())
+    } else if changed {
+        Err(refuse(format!(
+            "the {} host key of {} has changed, someone could be eavesdropping on you",
+            key_type, name
+        )))
+    } else {
+        Err(refuse(format!(
+            "the host {} is not known, connect to it with ssh once to check and add its key",
+            name
+        )))
+    }
+}
+
+/// the port of an ssh url, e.g. `ssh://git@host:2222/group/repo.git`, an scp-like url uses 22
+pub(crate) fn ssh_port(url: &str) -> u16 {
+    ["ssh://", "ssh+git://", "git+ssh://"]
+        .iter()
+        .find_map(|scheme| url.strip_prefix(scheme))
+        .and_then(|rest| rest.split('/').next())
+        .and_then(|authority| authority.rsplit('@').next())
+        .and_then(|host| host.rsplit_once(':'))
+        .and_then(|(_, port)| port.parse().ok())
+        .unwrap_or(22)
+}
+
+fn refuse(message: String) -> git2::Error {
+    git2::Error::new(ErrorCode::Certificate, ErrorClass::Ssh, message)
+}
+
+// the hosts of a line are patterns separated by commas, or a single hashed name
+fn matches_hosts(hosts: &str, name: &str) -> bool {
+    if let Some(hashed) = hosts.strip_prefix("|1|") {
+        let Some((salt, hash)) = hashed.split_once('|') else {
+            return false;
+        };
+        return match (STANDARD.decode(salt), STANDARD.decode(hash)) {
+            (Ok(salt), Ok(hash)) => match Hmac::<Sha1>::new_from_slice(&salt) {
+                Ok(mut mac) => {
+                    mac.update(name.as_bytes());
+                    mac.verify_slice(&hash).is_ok()
+                }
+                Err(_) => false,
+            },
+            _ => false,
+        };
+    }
+
+    let mut matched = false;
+    for pattern in hosts.split(',') {
+        match pattern.strip_prefix('!') {
+            // a negated pattern excludes the host from the whole line
+            Some(pattern) if wildcard(pattern.as_bytes(), name.as_bytes()) => return false,
+            Some(_) => {}
+            None => matched |= wildcard(pattern.as_bytes(), name.as_bytes()),
+        }
+    }
+    matched
+}
+
+fn wildcard(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|i| wildcard(rest, &name[i..])),
+        Some((b'?', rest)) => !name.is_empty() && wildcard(rest, &name[1..]),
+        Some((c, rest)) => {
+            name.first().is_some_and(|x| x.eq_ignore_ascii_case(c)) && wildcard(rest, &name[1..])
+        }
+    }
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use super::{check, ssh_port};
+    use crate::GitError;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use std::fs;
+
+    const KEY: &str = "AAAAC3NzaC1lZDI1NTE5QUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFB";
+    const OTHER_KEY: &str = "AAAAC3NzaC1lZDI1NTE5QkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJC";
+
+    #[test]
+    fn test_host_key_mismatch() {
+        let dir = std::env::temp_dir().join(format!("yoo-git-hosts-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("known_hosts");
+        fs::write(
+            &file,
+            format!(
+                "# comment\nexample.com ssh-ed25519 {0}\n*.example.net,!evil.example.net ssh-ed25519 {0}\n|1|MDEyMzQ1Njc4OWFiY2RlZmdoaWo=|B4JlprCduyj/8/3b1qnq1ugMSoY= ssh-ed25519 {0}\n",
+                KEY
+            ),
+        )
+        .unwrap();
+        let files = vec![file];
+        let key = STANDARD.decode(KEY).unwrap();
+        let other_key = STANDARD.decode(OTHER_KEY).unwrap();
+
+        assert!(check(&files, "example.com", 22, "ssh-ed25519", &key).is_ok());
+        assert!(check(&files, "git.EXAMPLE.net", 22, "ssh-ed25519", &key).is_ok());
+        // the hashed name of [example.com]:2222
+        assert!(check(&files, "example.com", 2222, "ssh-ed25519", &key).is_ok());
+
+        let err = check(&files, "example.com", 22, "ssh-ed25519", &other_key).unwrap_err();
+        assert_eq!(err.code(), git2::ErrorCode::Certificate);
+        assert!(err.message().contains("has changed"));
+        assert!(matches!(
+            GitError::new(err, "ssh://git@example.com/a.git", "HEAD"),
+            GitError::HostKey { .. }
+        ));
+
+        let err = check(&files, "evil.example.net", 22, "ssh-ed25519", &key).unwrap_err();
+        assert!(err.message().contains("not known"));
+        assert!(check(&files, "example.org", 22, "ssh-ed25519", &key).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_ssh_port() {
+        assert_eq!(ssh_port("ssh://git@example.com:2222/a/b.git"), 2222);
+        assert_eq!(ssh_port("git@example.com:a/b.git"), 22);
+    }
+}
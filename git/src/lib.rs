@@ -1,8 +1,31 @@
 use crate::exec::exec_git_command;
 use anyhow::{Context, Result};
-use git2::{Repository, StatusOptions};
+use git2::{
+    build::CheckoutBuilder, FileMode, IndexAddOption, Oid, Repository, RepositoryInitOptions, Tree,
+};
+use std::{fs, path::Path};
 
 mod exec;
+mod history;
+mod known_hosts;
+mod remote;
+mod status;
+
+pub use history::{AheadBehind, ChangeKind, CommitInfo, DiffStats, FileStat, LogFilter};
+pub use remote::{
+    FetchSummary, GitError, PassphrasePrompt, ProgressCallback, PullOutcome, RefUpdate, RemoteInfo,
+    RemoteOptions, TransferProgress,
+};
+pub use status::{Rename, StatusChange, WorkingTreeStatus};
+
+/// The result of merging the changes between two trees into the working tree
+#[derive(Debug, Default)]
+pub struct MergeSummary {
+    /// The paths changed between the two trees
+    pub changed: Vec<String>,
+    /// The paths which are left with conflict markers
+    pub conflicts: Vec<String>,
+}
 
 pub struct GitRepo {
     repo: Repository,
@@ -17,9 +40,105 @@ pub fn open_repo(path: &str) -> Result<GitRepo> {
     })
 }
 
-pub fn clone(repo: &str, path: &str) -> Result<GitRepo> {
-    exec_git_command(&vec!["clone", repo, path], None)?;
-    open_repo(path)
+/// init a repository whose initial branch is master
+pub fn init(path: &str) -> Result<GitRepo> {
+    let mut opts = RepositoryInitOptions::new();
+    opts.initial_head("master");
+    let repo =
+        Repository::init_opts(path, &opts).with_context(|| "Failed to init the repository")?;
+    Ok(GitRepo {
+        repo,
+        working_dir: None,
+    })
+}
+
+/// the version of the git binary, e.g. `2.39.2`
+pub fn version() -> Result<String> {
+    let output = exec_git_command(&vec!["--version"], None)?;
+    Ok(output.trim().trim_start_matches("git version ").to_string())
+}
+
+/// clone the repo with libgit2, the git binary is only run as the fallback
+pub fn clone(repo: &str, path: &str, options: &RemoteOptions) -> Result<GitRepo> {
+    let result = git2::build::RepoBuilder::new()
+        .fetch_options(options.fetch_options(repo))
+        .clone(repo, Path::new(path))
+        .map_err(|err| GitError::new(err, repo, "HEAD"));
+
+    let repo = fallback(result, options, || {
+        remove_partial_clone(path)?;
+        exec_git_command(&vec!["clone", repo, path], None)?;
+        Ok(Repository::open(path)?)
+    })?;
+    Ok(GitRepo {
+        repo,
+        working_dir: None,
+    })
+}
+
+/// clone a bare mirror of the repo, all the refs are kept in sync by `update_mirror`
+pub fn clone_mirror(repo: &str, path: &str, options: &RemoteOptions) -> Result<()> {
+    let result = git2::build::RepoBuilder::new()
+        .bare(true)
+        .remote_create(|repo, name, url| repo.remote_with_fetch(name, url, "+refs/*:refs/*"))
+        .fetch_options(options.fetch_options(repo))
+        .clone(repo, Path::new(path))
+        .and_then(|repo| repo.config()?.set_bool("remote.origin.mirror", true))
+        .map_err(|err| GitError::new(err, repo, "HEAD"));
+
+    fallback(result, options, || {
+        remove_partial_clone(path)?;
+        exec_git_command(&vec!["clone", "--mirror", repo, path], None)?;
+        Ok(())
+    })
+}
+
+pub fn update_mirror(path: &str, options: &RemoteOptions) -> Result<()> {
+    let repo = Repository::open_bare(path).with_context(|| "Failed to open the mirror")?;
+    let mut remote = repo
+        .find_remote("origin")
+        .with_context(|| "Failed to find the remote origin of the mirror")?;
+    let url = remote.url().unwrap_or_default().to_string();
+
+    let mut fetch_options = options.fetch_options(&url);
+    fetch_options.prune(git2::FetchPrune::On);
+    let result = remote
+        .fetch(&[] as &[&str], Some(&mut fetch_options), None)
+        .map_err(|err| GitError::new(err, &url, "HEAD"));
+
+    fallback(result, options, || {
+        exec_git_command(&vec!["remote", "update", "--prune"], Some(path))?;
+        Ok(())
+    })
+}
+
+// run the git binary when the native operation fails and the fallback is enabled,
+// a rejected push is never retried since the binary is rejected in the same way
+fn fallback<T, F>(result: Result<T, GitError>, options: &RemoteOptions, binary: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    match result {
+        Ok(value) => Ok(value),
+        Err(err) if options.fallback && !matches!(err, GitError::Rejected { .. }) => {
+            tracing::warn!("{}, running the git binary instead", err);
+            binary()
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+// a path or a file url
+fn is_local(url: &str) -> bool {
+    url.starts_with("file://") || !url.contains("://") && !url.contains('@')
+}
+
+// libgit2 may leave a partial clone behind
+fn remove_partial_clone(path: &str) -> Result<()> {
+    if Path::new(path).exists() {
+        fs::remove_dir_all(path).with_context(|| "Failed to remove the partial clone")?;
+    }
+    Ok(())
 }
 
 impl GitRepo {
@@ -30,6 +149,21 @@ impl GitRepo {
         Ok(())
     }
 
+    /// whether the HEAD is unborn, i.e. there is no commit yet
+    pub fn is_empty(&self) -> bool {
+        self.repo.is_empty().unwrap_or(false)
+    }
+
+    pub fn has_remote(&self, name: &str) -> bool {
+        self.repo.find_remote(name).is_ok()
+    }
+
+    /// the url of the remote, `None` when there is no such remote
+    pub fn remote_url(&self, name: &str) -> Option<String> {
+        let remote = self.repo.find_remote(name).ok()?;
+        remote.url().map(|x| x.to_string())
+    }
+
     pub fn change_working_dir(&mut self, dir: Option<String>) -> Result<()> {
         self.working_dir = dir;
         Ok(())
@@ -71,75 +205,208 @@ impl GitRepo {
         Ok(())
     }
 
-    pub fn has_uncommitted_changes(&self) -> Result<bool> {
-        let mut status_opts = StatusOptions::new();
-        status_opts.include_ignored(false);
-        let statuses = self
+    /// point the branch at the reference, which can be a branch of origin, a tag or a commit,
+    /// and check it out
+    pub fn checkout_branch_at(&self, branch: &str, reference: &str) -> Result<()> {
+        let object = self
             .repo
-            .statuses(Some(&mut status_opts))
-            .with_context(|| "Failed to get the status of the repository")?;
+            .revparse_single(&format!("origin/{}", reference))
+            .or_else(|_| self.repo.revparse_single(reference))
+            .with_context(|| format!("Failed to find the reference {}", reference))?;
+        let commit = object
+            .peel_to_commit()
+            .with_context(|| "Failed to get the commit")?;
+
+        self.repo
+            .checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().force()))
+            .with_context(|| "Failed to checkout the tree")?;
+        self.repo
+            .set_head_detached(commit.id())
+            .with_context(|| "Failed to set the head")?;
+        self.repo
+            .branch(branch, &commit, true)
+            .with_context(|| "Failed to create the branch")?;
+        self.repo
+            .set_head(&format!("refs/heads/{}", branch))
+            .with_context(|| "Failed to set the head")?;
+
+        Ok(())
+    }
+
+    /// check out the branch of origin as a local branch which tracks it
+    pub fn checkout_remote_branch(&self, branch: &str) -> Result<()> {
+        self.repo
+            .find_branch(&format!("origin/{}", branch), git2::BranchType::Remote)
+            .with_context(|| format!("There is no branch {} on the remote origin", branch))?;
 
-        statuses.iter().for_each(|s| {
-            tracing::info!(
-                "status: {:?}, path: {:?}",
-                s.status(),
-                s.index_to_workdir().map(|p| p.new_file().path())
-            );
+        self.checkout_branch_at(branch, branch)?;
+        self.repo
+            .find_branch(branch, git2::BranchType::Local)
+            .with_context(|| "Failed to find the branch")?
+            .set_upstream(Some(&format!("origin/{}", branch)))
+            .with_context(|| "Failed to set the upstream")?;
+
+        Ok(())
+    }
+
+    /// push the branch to the same branch of origin, the git binary is only run as the fallback
+    pub fn push(&self, branch: &str, options: &RemoteOptions) -> Result<()> {
+        let working_dir = self.git_dir();
+        let result = self
+            .repo
+            .find_branch(branch, git2::BranchType::Local)
+            .map_err(GitError::Git)
+            .and_then(|_| self.push_native(&format!("refs/heads/{}", branch), options));
+        fallback(result, options, || {
+            exec_git_command(&vec!["push", "origin", branch], working_dir)?;
+            Ok(())
+        })
+    }
+
+    /// push the tag to origin, the git binary is only run as the fallback
+    pub fn push_tag(&self, tag: &str, options: &RemoteOptions) -> Result<()> {
+        let reference = format!("refs/tags/{}", tag);
+        self.repo
+            .find_reference(&reference)
+            .with_context(|| format!("Failed to find the tag {}", tag))?;
+        fallback(self.push_native(&reference, options), options, || {
+            exec_git_command(&vec!["push", "origin", &reference], self.git_dir())?;
+            Ok(())
+        })
+    }
+
+    // the directory the git binary runs in
+    fn git_dir(&self) -> Option<&str> {
+        self.working_dir
+            .as_deref()
+            .or_else(|| self.repo.workdir().and_then(|x| x.to_str()))
+    }
+
+    fn push_native(&self, reference: &str, options: &RemoteOptions) -> Result<(), GitError> {
+        let mut remote = self.repo.find_remote("origin").map_err(GitError::Git)?;
+        let url = remote.url().unwrap_or_default().to_string();
+
+        // the remote reports a rejected reference through the callback instead of an error
+        let rejected = std::cell::RefCell::new(None);
+        let mut callbacks = options.callbacks(&url);
+        callbacks.push_update_reference(|reference, status| {
+            if let Some(message) = status {
+                *rejected.borrow_mut() = Some((reference.to_string(), message.to_string()));
+            }
+            Ok(())
         });
+        // the local transport of libgit2 doesn't count the sent bytes
+        if let (Some(progress), true) = (&options.progress, is_local(&url)) {
+            callbacks.push_transfer_progress(move |current, total, _| {
+                progress(TransferProgress::Sending {
+                    current,
+                    total,
+                    bytes: None,
+                });
+            });
+        }
+
+        let mut push_options = git2::PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+        remote
+            .push(&[format!("{0}:{0}", reference)], Some(&mut push_options))
+            .map_err(|err| GitError::new(err, &url, reference))?;
+
+        let rejected = rejected.borrow_mut().take();
+        match rejected {
+            Some((reference, message)) => Err(GitError::Rejected { reference, message }),
+            None => Ok(()),
+        }
+    }
+
+    /// stage all the changes of the working tree, including the deleted files, and commit them on HEAD
+    pub fn commit_all(&self, message: &str) -> Result<()> {
+        let mut index = self
+            .repo
+            .index()
+            .with_context(|| "Failed to get the index")?;
+        index
+            .add_all(["*"].iter(), IndexAddOption::DEFAULT, None)
+            .with_context(|| "Failed to stage the changes")?;
+        index
+            .update_all(["*"].iter(), None)
+            .with_context(|| "Failed to stage the changes")?;
+        self.commit_index(&mut index, message)
+    }
+
+    /// stage the changes of the tracked files and commit them on HEAD with the staged ones,
+    /// the untracked files are left out
+    pub fn commit_tracked(&self, message: &str) -> Result<()> {
+        let mut index = self
+            .repo
+            .index()
+            .with_context(|| "Failed to get the index")?;
+        index
+            .update_all(["*"].iter(), None)
+            .with_context(|| "Failed to stage the changes")?;
+        self.commit_index(&mut index, message)
+    }
 
-        if !statuses.is_empty() {
-            return Ok(true);
+    /// stage the files, a missing one is removed, and commit them on HEAD with the staged ones,
+    /// the other changes are left out
+    pub fn commit_paths(&self, paths: &[String], message: &str) -> Result<()> {
+        let workdir = self
+            .repo
+            .workdir()
+            .with_context(|| "There is no working tree in a bare repository")?;
+        let mut index = self
+            .repo
+            .index()
+            .with_context(|| "Failed to get the index")?;
+        for path in paths {
+            if workdir.join(path).exists() {
+                index.add_path(Path::new(path))
+            } else {
+                index.remove_path(Path::new(path))
+            }
+            .with_context(|| format!("Failed to stage {}", path))?;
         }
+        self.commit_index(&mut index, message)
+    }
+
+    // write the index and commit it on HEAD
+    fn commit_index(&self, index: &mut git2::Index, message: &str) -> Result<()> {
+        index.write().with_context(|| "Failed to write the index")?;
+
+        let tree_id = index
+            .write_tree()
+            .with_context(|| "Failed to write the tree")?;
+        let tree = self
+            .repo
+            .find_tree(tree_id)
+            .with_context(|| "Failed to find the tree")?;
+        // there is no parent in a newly initialized repository
+        let parent = if self.is_empty() {
+            None
+        } else {
+            let commit = self
+                .repo
+                .head()
+                .with_context(|| "Failed to get the head")?
+                .peel_to_commit()
+                .with_context(|| "Failed to get the commit")?;
+            Some(commit)
+        };
+        let signature = self.repo.signature().with_context(|| {
+            "Failed to get the signature, please config the user.name and user.email of git"
+        })?;
+
+        self.repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parent.iter().collect::<Vec<_>>(),
+            )
+            .with_context(|| "Failed to commit the changes")?;
 
-        Ok(false)
-    }
-
-    pub fn push(&self, branch: &str) -> Result<()> {
-        // let mut remote = self
-        //     .repo
-        //     .find_remote("origin")
-        //     .with_context(|| "Failed to push the repository")?;
-        //
-        // let branch = self
-        //     .repo
-        //     .find_branch(branch, git2::BranchType::Local)
-        //     .with_context(|| "Failed to find the branch")?;
-        //
-        // let refs = branch.into_reference();
-        //
-        // let name = refs.name().with_context(|| "The reference name is none")?;
-        //
-        // let mut callbacks = git2::RemoteCallbacks::new();
-        //
-        // callbacks.credentials(|_url, _username_from_url, _allowed_types| {
-        //     // tracing::info!("allowed types: {:?}", _allowed_types);
-        //
-        //     if _allowed_types.contains(CredentialType::SSH_KEY) {
-        //         let home_dir = env::var("HOME").map_err(|_| git2::Error::from_str("Failed to get home dir"))?;
-        //         let private_key_file = format!("{}/.ssh/id_ed25519", home_dir);
-        //         let private_key_path = std::path::Path::new(&private_key_file);
-        //         Cred::ssh_key(
-        //             _username_from_url.unwrap(),
-        //             None,
-        //             private_key_path,
-        //             None,
-        //         )
-        //     } else {
-        //         tracing::error!("The cli only support ssh protocol to interact with git");
-        //         Err(git2::Error::from_str("The cli only support use ssh protocol to interact with git"))
-        //     }
-        // });
-        //
-        // let mut options = git2::PushOptions::new();
-        //
-        // options.remote_callbacks(callbacks);
-        //
-        // // push the code to master branch
-        // remote
-        //     .push(&[name], Some(&mut options))
-        //     .with_context(|| "Failed to push the code")?;
-
-        exec_git_command(&vec!["push", "origin", branch], self.working_dir.as_deref())?;
         Ok(())
     }
 
@@ -165,4 +432,246 @@ impl GitRepo {
             .with_context(|| "Failed to set the remote")?;
         Ok(())
     }
+
+    /// the id of the commit HEAD points at
+    pub fn head_commit(&self) -> Result<String> {
+        let commit = self
+            .repo
+            .head()
+            .with_context(|| "Failed to get the head")?
+            .peel_to_commit()
+            .with_context(|| "Failed to get the commit")?;
+        Ok(commit.id().to_string())
+    }
+
+    /// the id of the commit the local branch points at
+    pub fn branch_commit(&self, branch: &str) -> Result<String> {
+        let commit = self
+            .repo
+            .find_branch(branch, git2::BranchType::Local)
+            .with_context(|| format!("Failed to find the branch {}", branch))?
+            .get()
+            .peel_to_commit()
+            .with_context(|| "Failed to get the commit")?;
+        Ok(commit.id().to_string())
+    }
+
+    /// write the files of the directory into the object database as a tree, `.git` is skipped
+    pub fn write_tree_from_dir(&self, dir: &Path) -> Result<String> {
+        match self.write_dir(dir)? {
+            Some(oid) => Ok(oid.to_string()),
+            None => Err(anyhow::Error::msg(format!(
+                "There is no file in the directory {}",
+                dir.display()
+            ))),
+        }
+    }
+
+    // an empty directory can not be stored in git, so there is no tree for it
+    fn write_dir(&self, dir: &Path) -> Result<Option<Oid>> {
+        let mut builder = self
+            .repo
+            .treebuilder(None)
+            .with_context(|| "Failed to create the tree builder")?;
+
+        let entries = dir
+            .read_dir()
+            .with_context(|| format!("Failed to read the directory {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry.with_context(|| "Failed to read the directory entry")?;
+            if entry.file_name() == ".git" {
+                continue;
+            }
+
+            let path = entry.path();
+            let file_type = entry
+                .file_type()
+                .with_context(|| "Failed to get the file type")?;
+
+            let (oid, mode) = if file_type.is_dir() {
+                match self.write_dir(&path)? {
+                    Some(oid) => (oid, FileMode::Tree),
+                    None => continue,
+                }
+            } else if file_type.is_symlink() {
+                let target = fs::read_link(&path)
+                    .with_context(|| format!("Failed to read the link {}", path.display()))?;
+                let oid = self
+                    .repo
+                    .blob(target.to_string_lossy().as_bytes())
+                    .with_context(|| "Failed to write the blob")?;
+                (oid, FileMode::Link)
+            } else {
+                let oid = self
+                    .repo
+                    .blob_path(&path)
+                    .with_context(|| format!("Failed to write the blob of {}", path.display()))?;
+                let metadata = entry
+                    .metadata()
+                    .with_context(|| "Failed to get the metadata")?;
+                if is_executable(&metadata) {
+                    (oid, FileMode::BlobExecutable)
+                } else {
+                    (oid, FileMode::Blob)
+                }
+            };
+
+            builder
+                .insert(entry.file_name(), oid, mode.into())
+                .with_context(|| "Failed to insert the tree entry")?;
+        }
+
+        if builder.is_empty() {
+            return Ok(None);
+        }
+
+        let oid = builder
+            .write()
+            .with_context(|| "Failed to write the tree")?;
+        Ok(Some(oid))
+    }
+
+    /// apply the changes from the base tree to their tree onto HEAD with a three-way merge,
+    /// the result is checked out and the conflicts are left in the index and the working tree
+    pub fn merge_trees_into_head(&self, base: &str, theirs: &str) -> Result<MergeSummary> {
+        let base = self.find_tree(base)?;
+        let theirs = self.find_tree(theirs)?;
+        let ours = self
+            .repo
+            .head()
+            .with_context(|| "Failed to get the head")?
+            .peel_to_tree()
+            .with_context(|| "Failed to get the tree of the head")?;
+
+        let diff = self
+            .repo
+            .diff_tree_to_tree(Some(&base), Some(&theirs), None)
+            .with_context(|| "Failed to diff the trees")?;
+        let changed = diff
+            .deltas()
+            .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()))
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+
+        let mut merged = self
+            .repo
+            .merge_trees(&base, &ours, &theirs, None)
+            .with_context(|| "Failed to merge the trees")?;
+
+        let conflicts = merged
+            .conflicts()
+            .with_context(|| "Failed to get the conflicts")?
+            .filter_map(|conflict| conflict.ok())
+            .filter_map(|conflict| conflict.our.or(conflict.their).or(conflict.ancestor))
+            .map(|entry| String::from_utf8_lossy(&entry.path).to_string())
+            .collect();
+
+        self.repo
+            .checkout_index(
+                Some(&mut merged),
+                Some(
+                    CheckoutBuilder::new()
+                        .force()
+                        .allow_conflicts(true)
+                        .conflict_style_merge(true),
+                ),
+            )
+            .with_context(|| "Failed to checkout the merged files")?;
+
+        // keep the conflicts in the index so that git reports them as unmerged
+        let mut index = self
+            .repo
+            .index()
+            .with_context(|| "Failed to get the index")?;
+        index.clear().with_context(|| "Failed to clear the index")?;
+        for entry in merged.iter() {
+            index
+                .add(&entry)
+                .with_context(|| "Failed to update the index")?;
+        }
+        index.write().with_context(|| "Failed to write the index")?;
+
+        Ok(MergeSummary { changed, conflicts })
+    }
+
+    fn find_tree(&self, id: &str) -> Result<Tree<'_>> {
+        let oid = Oid::from_str(id).with_context(|| format!("Invalid tree id {}", id))?;
+        self.repo
+            .find_tree(oid)
+            .with_context(|| format!("Failed to find the tree {}", id))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_: &fs::Metadata) -> bool {
+    false
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    #[test]
+    fn test_merge_trees_into_head() {
+        let dir = std::env::temp_dir().join(format!("yoo-git-merge-{}", std::process::id()));
+        let (base_dir, their_dir, repo_dir) =
+            (dir.join("base"), dir.join("theirs"), dir.join("repo"));
+        let write = |dir: &std::path::Path, files: &[(&str, &str)]| {
+            fs::create_dir_all(dir).unwrap();
+            for (name, content) in files {
+                fs::write(dir.join(name), content).unwrap();
+            }
+        };
+
+        write(
+            &base_dir,
+            &[("a", "a\n"), ("b", "b\n"), ("c", "c\n"), ("d", "d\n")],
+        );
+        // b is changed cleanly, c is changed on both sides and d is deleted upstream
+        write(
+            &their_dir,
+            &[("a", "a\n"), ("b", "bb\n"), ("c", "theirs\n")],
+        );
+        write(
+            &repo_dir,
+            &[("a", "a\n"), ("b", "b\n"), ("c", "ours\n"), ("d", "d\n")],
+        );
+
+        let repo = crate::init(repo_dir.to_str().unwrap()).unwrap();
+        let mut config = repo.repo.config().unwrap();
+        config.set_str("user.name", "yoo").unwrap();
+        config.set_str("user.email", "yoo@yoo").unwrap();
+        repo.commit_all("init").unwrap();
+
+        let base = repo.write_tree_from_dir(&base_dir).unwrap();
+        let theirs = repo.write_tree_from_dir(&their_dir).unwrap();
+        // .git is left out of the tree
+        assert!(repo
+            .repo
+            .find_tree(git2::Oid::from_str(&repo.write_tree_from_dir(&repo_dir).unwrap()).unwrap())
+            .unwrap()
+            .get_name(".git")
+            .is_none());
+
+        let mut summary = repo.merge_trees_into_head(&base, &theirs).unwrap();
+        summary.changed.sort();
+        assert_eq!(summary.changed, vec!["b", "c", "d"]);
+        assert_eq!(summary.conflicts, vec!["c"]);
+
+        assert_eq!(fs::read_to_string(repo_dir.join("a")).unwrap(), "a\n");
+        assert_eq!(fs::read_to_string(repo_dir.join("b")).unwrap(), "bb\n");
+        assert!(!repo_dir.join("d").exists());
+        let conflicted = fs::read_to_string(repo_dir.join("c")).unwrap();
+        assert!(conflicted.contains("<<<<<<<") && conflicted.contains("theirs"));
+        assert_eq!(repo.status().unwrap().conflicted, vec!["c".to_string()]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
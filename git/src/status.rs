@@ -0,0 +1,236 @@
+use anyhow::{Context, Result};
+use git2::{Status, StatusEntry, StatusOptions};
+
+use crate::{ChangeKind, GitRepo};
+
+/// A file changed in the index or in the working tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusChange {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+/// A renamed file, staged or not
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    pub from: String,
+    pub to: String,
+    pub staged: bool,
+}
+
+/// The state of the working tree, the ignored files are left out
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkingTreeStatus {
+    /// The changes in the index, which the next commit includes
+    pub staged: Vec<StatusChange>,
+    /// The changes of the tracked files which are not staged
+    pub modified: Vec<StatusChange>,
+    pub untracked: Vec<String>,
+    /// The files with unresolved conflicts
+    pub conflicted: Vec<String>,
+    /// The renames, they are not listed as staged or modified
+    pub renamed: Vec<Rename>,
+    /// How many changes are stashed
+    pub stashes: usize,
+}
+
+impl WorkingTreeStatus {
+    /// whether there is nothing to commit, the untracked files included
+    pub fn is_clean(&self) -> bool {
+        !self.has_changes() && self.untracked.is_empty()
+    }
+
+    /// whether the tracked files are changed, the untracked files don't count
+    pub fn has_changes(&self) -> bool {
+        !self.staged.is_empty()
+            || !self.modified.is_empty()
+            || !self.conflicted.is_empty()
+            || !self.renamed.is_empty()
+    }
+}
+
+impl GitRepo {
+    /// the changes of the index and the working tree, and the number of the stashes
+    pub fn status(&self) -> Result<WorkingTreeStatus> {
+        let mut options = StatusOptions::new();
+        options
+            .include_ignored(false)
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .renames_head_to_index(true)
+            .renames_index_to_workdir(true);
+        let statuses = self
+            .repo
+            .statuses(Some(&mut options))
+            .with_context(|| "Failed to get the status of the repository")?;
+
+        let mut report = WorkingTreeStatus::default();
+        for entry in statuses.iter() {
+            let status = entry.status();
+            let path = entry.path().unwrap_or_default().to_string();
+
+            if status.is_conflicted() {
+                report.conflicted.push(path);
+                continue;
+            }
+            if status.is_wt_new() {
+                report.untracked.push(path);
+                continue;
+            }
+
+            if status.is_index_renamed() {
+                report.renamed.push(rename(&entry, true));
+            } else if let Some(kind) = index_change(status) {
+                report.staged.push(StatusChange {
+                    path: path.clone(),
+                    kind,
+                });
+            }
+            if status.is_wt_renamed() {
+                report.renamed.push(rename(&entry, false));
+            } else if let Some(kind) = workdir_change(status) {
+                report.modified.push(StatusChange { path, kind });
+            }
+        }
+
+        // every stash is an entry of the reflog of refs/stash
+        report.stashes = match self.repo.reflog("refs/stash") {
+            Ok(reflog) => reflog.len(),
+            Err(_) => 0,
+        };
+
+        Ok(report)
+    }
+
+    /// whether the tracked files are changed, see `status` for the details
+    pub fn has_uncommitted_changes(&self) -> Result<bool> {
+        Ok(self.status()?.has_changes())
+    }
+
+    /// stash the changes of the working tree, including the untracked files
+    pub fn stash(&mut self, message: &str) -> Result<()> {
+        let signature = self.repo.signature().with_context(|| {
+            "Failed to get the signature, please config the user.name and user.email of git"
+        })?;
+        self.repo
+            .stash_save(
+                &signature,
+                message,
+                Some(git2::StashFlags::INCLUDE_UNTRACKED),
+            )
+            .with_context(|| "Failed to stash the changes")?;
+        Ok(())
+    }
+}
+
+fn index_change(status: Status) -> Option<ChangeKind> {
+    if status.is_index_new() {
+        Some(ChangeKind::Added)
+    } else if status.is_index_deleted() {
+        Some(ChangeKind::Deleted)
+    } else if status.is_index_modified() {
+        Some(ChangeKind::Modified)
+    } else if status.is_index_typechange() {
+        Some(ChangeKind::Other)
+    } else {
+        None
+    }
+}
+
+fn workdir_change(status: Status) -> Option<ChangeKind> {
+    if status.is_wt_deleted() {
+        Some(ChangeKind::Deleted)
+    } else if status.is_wt_modified() {
+        Some(ChangeKind::Modified)
+    } else if status.is_wt_typechange() {
+        Some(ChangeKind::Other)
+    } else {
+        None
+    }
+}
+
+fn rename(entry: &StatusEntry, staged: bool) -> Rename {
+    let delta = if staged {
+        entry.head_to_index()
+    } else {
+        entry.index_to_workdir()
+    };
+    let path = |file: Option<&std::path::Path>| {
+        file.map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    Rename {
+        from: path(delta.as_ref().and_then(|x| x.old_file().path())),
+        to: path(delta.as_ref().and_then(|x| x.new_file().path())),
+        staged,
+    }
+}
+
+// test
+#[cfg(test)]
+mod test {
+    use crate::ChangeKind;
+    use std::fs;
+
+    #[test]
+    fn test_status() {
+        let dir = std::env::temp_dir().join(format!("yoo-git-status-{}", std::process::id()));
+        let mut repo = crate::init(dir.to_str().unwrap()).unwrap();
+        let mut config = repo.repo.config().unwrap();
+        config.set_str("user.name", "yoo").unwrap();
+        config.set_str("user.email", "yoo@yoo").unwrap();
+
+        fs::write(dir.join("a"), "a\na\na\n").unwrap();
+        fs::write(dir.join("b"), "b\n").unwrap();
+        repo.commit_all("init").unwrap();
+        assert!(repo.status().unwrap().is_clean());
+
+        fs::write(dir.join("b"), "bb\n").unwrap();
+        fs::write(dir.join("c"), "c\n").unwrap();
+        fs::rename(dir.join("a"), dir.join("d")).unwrap();
+        let mut index = repo.repo.index().unwrap();
+        index.remove_path(std::path::Path::new("a")).unwrap();
+        index.add_path(std::path::Path::new("d")).unwrap();
+        index.write().unwrap();
+
+        let status = repo.status().unwrap();
+        assert!(status.staged.is_empty());
+        assert_eq!(status.modified.len(), 1);
+        assert_eq!(status.modified[0].kind, ChangeKind::Modified);
+        assert_eq!(status.untracked, vec!["c".to_string()]);
+        assert_eq!(status.renamed.len(), 1);
+        assert_eq!(
+            (
+                status.renamed[0].from.as_str(),
+                status.renamed[0].to.as_str()
+            ),
+            ("a", "d")
+        );
+        assert!(status.renamed[0].staged);
+
+        repo.stash("test").unwrap();
+        let status = repo.status().unwrap();
+        assert!(status.is_clean());
+        assert_eq!(status.stashes, 1);
+
+        // the untracked files are left out of the commit
+        fs::write(dir.join("b"), "bbb\n").unwrap();
+        fs::write(dir.join("e"), "e\n").unwrap();
+        repo.commit_tracked("change b").unwrap();
+        let status = repo.status().unwrap();
+        assert!(!status.has_changes());
+        assert_eq!(status.untracked, vec!["e".to_string()]);
+
+        // only the given paths are committed
+        fs::write(dir.join("b"), "bbbb\n").unwrap();
+        fs::write(dir.join("a"), "aa\n").unwrap();
+        repo.commit_paths(&["b".to_string()], "change b again")
+            .unwrap();
+        let status = repo.status().unwrap();
+        assert_eq!(status.modified.len(), 1);
+        assert_eq!(status.modified[0].path, "a");
+        assert_eq!(status.untracked, vec!["e".to_string()]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}